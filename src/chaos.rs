@@ -0,0 +1,163 @@
+use std::str::FromStr;
+use std::time::Duration;
+
+use game::Direction;
+
+// How long the warning about changed controls stays up after they change
+pub const WARNING_DURATION: Duration = Duration::from_millis(1500);
+
+// A remapping of the directions the player steers in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ControlTransform {
+    Normal,
+    // Every direction steers the opposite way
+    Reverse,
+    // Every direction steers a quarter turn clockwise or counterclockwise of itself
+    Clockwise,
+    Counterclockwise,
+}
+
+impl ControlTransform {
+    pub fn apply(self, dir: Direction) -> Direction {
+        match self {
+            ControlTransform::Normal => dir,
+            ControlTransform::Reverse => dir.reverse(),
+            ControlTransform::Clockwise => clockwise(dir),
+            ControlTransform::Counterclockwise => clockwise(dir).reverse(),
+        }
+    }
+
+    // Warning shown when the controls change to this transform
+    pub fn warning(self) -> &'static str {
+        match self {
+            ControlTransform::Normal => "CONTROLS NORMAL",
+            ControlTransform::Reverse => "CONTROLS REVERSED!",
+            ControlTransform::Clockwise => "CONTROLS ROTATED RIGHT!",
+            ControlTransform::Counterclockwise => "CONTROLS ROTATED LEFT!",
+        }
+    }
+}
+
+impl FromStr for ControlTransform {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "normal" => Ok(ControlTransform::Normal),
+            "reverse" => Ok(ControlTransform::Reverse),
+            "clockwise" => Ok(ControlTransform::Clockwise),
+            "counterclockwise" => Ok(ControlTransform::Counterclockwise),
+            _ => Err(format!("Unknown control transform: {}", s)),
+        }
+    }
+}
+
+fn clockwise(dir: Direction) -> Direction {
+    match dir {
+        Direction::Up => Direction::Right,
+        Direction::Right => Direction::Down,
+        Direction::Down => Direction::Left,
+        Direction::Left => Direction::Up,
+    }
+}
+
+// Controls that keep changing: the transforms are cycled through in order, each applying for one
+// interval, starting over with the first after the last
+#[derive(Clone, Debug)]
+pub struct ChaosControls {
+    pub interval: Duration,
+    pub transforms: Vec<ControlTransform>,
+}
+
+impl ChaosControls {
+    pub fn new(interval: Duration) -> Self {
+        ChaosControls {
+            interval: interval,
+            transforms: vec![ControlTransform::Normal, ControlTransform::Reverse],
+        }
+    }
+
+    // Index of the interval the given time since the start of the game falls in
+    fn period(&self, elapsed: Duration) -> u32 {
+        (elapsed.as_millis() / self.interval.as_millis().max(1)) as u32
+    }
+
+    fn transform_in(&self, period: u32) -> ControlTransform {
+        if self.transforms.is_empty() {
+            return ControlTransform::Normal;
+        }
+        self.transforms[period as usize % self.transforms.len()]
+    }
+
+    // Transform applying at the given time since the start of the game
+    pub fn transform_at(&self, elapsed: Duration) -> ControlTransform {
+        self.transform_in(self.period(elapsed))
+    }
+
+    // Warning to show at the given time since the start of the game, if the controls changed
+    // recently.  The controls the game starts with aren't a change, so they aren't warned about.
+    pub fn warning_at(&self, elapsed: Duration) -> Option<&'static str> {
+        let period = self.period(elapsed);
+        if period == 0 || self.transform_in(period) == self.transform_in(period - 1) {
+            return None;
+        }
+        let since_change = elapsed - self.interval * period;
+        if since_change < WARNING_DURATION {
+            Some(self.transform_in(period).warning())
+        } else {
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::Direction::{Down, Left, Right, Up};
+    use super::*;
+
+    fn chaos() -> ChaosControls {
+        ChaosControls {
+            interval: Duration::from_secs(10),
+            transforms: vec![ControlTransform::Normal,
+                             ControlTransform::Reverse,
+                             ControlTransform::Clockwise],
+        }
+    }
+
+    #[test]
+    fn transforms_cycle_once_per_interval() {
+        let chaos = chaos();
+        let at = |seconds| chaos.transform_at(Duration::from_secs(seconds));
+        assert_eq!(at(0), ControlTransform::Normal);
+        assert_eq!(at(9), ControlTransform::Normal);
+        assert_eq!(at(10), ControlTransform::Reverse);
+        assert_eq!(at(25), ControlTransform::Clockwise);
+        assert_eq!(at(30), ControlTransform::Normal);
+    }
+
+    #[test]
+    fn transforms_remap_directions() {
+        assert_eq!(ControlTransform::Reverse.apply(Up), Down);
+        assert_eq!(ControlTransform::Clockwise.apply(Up), Right);
+        assert_eq!(ControlTransform::Counterclockwise.apply(Up), Left);
+        assert_eq!(ControlTransform::Normal.apply(Left), Left);
+    }
+
+    #[test]
+    fn changes_are_warned_about_only_for_a_moment() {
+        let chaos = chaos();
+        let at = |millis| chaos.warning_at(Duration::from_millis(millis));
+        assert_eq!(at(0), None);
+        assert_eq!(at(10_000), Some("CONTROLS REVERSED!"));
+        assert_eq!(at(11_000), Some("CONTROLS REVERSED!"));
+        assert_eq!(at(12_000), None);
+    }
+
+    #[test]
+    fn a_single_transform_never_changes() {
+        let chaos = ChaosControls { transforms: vec![ControlTransform::Reverse], ..chaos() };
+        assert_eq!(chaos.transform_at(Duration::from_secs(0)), ControlTransform::Reverse);
+        assert_eq!(chaos.transform_at(Duration::from_secs(95)), ControlTransform::Reverse);
+        assert_eq!(chaos.warning_at(Duration::from_secs(10)), None);
+    }
+}
@@ -0,0 +1,97 @@
+use std::str::FromStr;
+use sdl2::GameControllerSubsystem;
+use sdl2::controller::{Button, GameController};
+
+use game::Direction;
+
+// Device the player steers the snake with, in addition to the keyboard
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum InputDevice {
+    Keyboard,
+    // The first game controller connected
+    AnyController,
+    // The game controller with the given name
+    Controller(String),
+}
+
+impl FromStr for InputDevice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        Ok(match s {
+               "keyboard" => InputDevice::Keyboard,
+               "controller" => InputDevice::AnyController,
+               name => InputDevice::Controller(name.to_string()),
+           })
+    }
+}
+
+// Names of the connected game controllers, any of which can be chosen as the input device
+pub fn connected_names(subsystem: &GameControllerSubsystem) -> Result<Vec<String>, String> {
+    let count = subsystem.num_joysticks()?;
+    Ok((0..count)
+           .filter(|&index| subsystem.is_game_controller(index))
+           .filter_map(|index| subsystem.name_for_index(index).ok())
+           .collect())
+}
+
+// Open the controller with the given device index if it is the input device the player chose
+pub fn open_if_chosen(subsystem: &GameControllerSubsystem,
+                      device: &InputDevice,
+                      index: u32)
+                      -> Option<GameController> {
+    if !subsystem.is_game_controller(index) {
+        return None;
+    }
+    let chosen = match *device {
+        InputDevice::Keyboard => false,
+        InputDevice::AnyController => true,
+        InputDevice::Controller(ref name) => {
+            subsystem.name_for_index(index).ok().as_ref() == Some(name)
+        }
+    };
+    if !chosen {
+        return None;
+    }
+    match subsystem.open(index) {
+        Ok(controller) => {
+            println!("Using controller {}", controller.name());
+            Some(controller)
+        }
+        Err(e) => {
+            println!("Warning: failed to open controller {}: {}", index, e);
+            None
+        }
+    }
+}
+
+// Open the first connected controller that is the input device the player chose, if any
+pub fn open_chosen(subsystem: &GameControllerSubsystem,
+                   device: &InputDevice)
+                   -> Result<Option<GameController>, String> {
+    let count = subsystem.num_joysticks()?;
+    Ok((0..count).filter_map(|index| open_if_chosen(subsystem, device, index)).next())
+}
+
+pub fn button_direction(button: Button) -> Option<Direction> {
+    match button {
+        Button::DPadUp => Some(Direction::Up),
+        Button::DPadDown => Some(Direction::Down),
+        Button::DPadLeft => Some(Direction::Left),
+        Button::DPadRight => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_devices_parse_from_their_names() {
+        assert_eq!("keyboard".parse(), Ok(InputDevice::Keyboard));
+        assert_eq!("controller".parse(), Ok(InputDevice::AnyController));
+        assert_eq!("Xbox 360 Controller".parse(),
+                   Ok(InputDevice::Controller("Xbox 360 Controller".to_string())));
+    }
+}
@@ -1,28 +1,120 @@
 use std::collections::VecDeque;
+use std::path::Path;
+use std::time::{Duration, Instant};
 use sdl2;
+use sdl2::controller::{Button, GameController};
 use sdl2::event::Event;
 use sdl2::keyboard::Keycode;
-use sdl2::pixels::Color;
 use sdl2::rect::Rect;
+use sdl2::render::BlendMode;
 use sdl2::video::FullscreenType;
-use preferences::Preferences;
+use rand::{self, Rng};
 
-use game::{Direction, GameState, Tile};
+use chaos::{ChaosControls, ControlTransform};
+use controller::{self, InputDevice};
+use game::{DEFAULT_LEVEL_SIZE, DIRECTIONS, Direction, EdgeBehavior, GameEvent, GameState,
+           Observers, OvertimeCondition, Rules, SnakePart, StartMode, Tile, TileIndex, Zone};
+use graveyard::Graveyard;
+use highscores::Highscores;
+use hud;
+use input;
+use rate::RateCounter;
+use replay::{Replay, ReplayPlayer};
+use settings::Settings;
+use signal;
+use spawn::FarthestFromHead;
+use storage;
+use summary;
+use text;
+use trace::Trace;
 
 pub struct Engine {
     game_state: GameState,
+    settings: Settings,
+    // Highscores of all levels; the game state only knows the one for its own level
+    highscores: Highscores,
+    // Where the player's snakes died on all levels
+    graveyard: Graveyard,
+    // Whether the game state is saved on exit and restored on the next start
+    auto_save: bool,
     tile_size: u32,
     event_pump: sdl2::EventPump,
     renderer: sdl2::render::Renderer<'static>,
     mouse: sdl2::mouse::MouseUtil,
+    controller_subsystem: sdl2::GameControllerSubsystem,
+    // Controller used for steering; only its events are handled, and without one only the
+    // keyboard is used
+    controller: Option<GameController>,
+    // Additional boards shown next to the main one
+    boards: Vec<GameState>,
+    // Whether snakes are steered by GameState::suggest_direction instead of the player
+    autopilot: bool,
+    // Whether holding the key for the snake's current direction speeds it up
+    turbo: bool,
+    // Text currently shown in the window title
+    title: String,
+    // Measured game ticks and rendered frames per second, shown in the title in debug mode
+    tick_rate: RateCounter,
+    frame_rate: RateCounter,
+    // Recording of the current game, and the player for reviewing it once the game is over
+    replay: Replay,
+    replay_player: Option<ReplayPlayer>,
+    // Whether debugging aids are available, and whether tile coordinates, the floor reachable
+    // from the snake's head and the autopilot's scores for each direction are shown
+    debug: bool,
+    show_coordinates: bool,
+    show_reachable: bool,
+    show_bot_scores: bool,
+    // Number of frames rendered so far, which animations are timed by
+    frame: u32,
+    // Frames left in the current screen shake, and how strong it started out, in pixels
+    shake_frames: u32,
+    shake_intensity: u32,
+    // Callbacks for events in the main game
+    observers: Observers,
+    // Log of every update of the main game, if requested
+    trace: Option<Trace>,
+    // Controls that keep changing in the chaos mode, and when the snake of the current game
+    // started moving, which is what they change relative to
+    chaos: Option<ChaosControls>,
+    game_start: Option<Instant>,
 }
 
+// Number of frames between game ticks at normal speed and with turbo engaged
+const TICK_INTERVAL: u32 = 10;
+const TURBO_TICK_INTERVAL: u32 = 5;
+
+// Largest supported render scale, keeping the window within reason
+const MAX_RENDER_SCALE: f32 = 8.0;
+
+// Number of frames a screen shake lasts, and its strength in pixels for dying and breaking walls;
+// shakes are kept short and small so they don't get uncomfortable
+const SHAKE_FRAMES: u32 = 12;
+const DEATH_SHAKE: u32 = 3;
+const WALL_BREAK_SHAKE: u32 = 2;
+
 impl Engine {
+    // Callbacks for events in the game played by the player
+    pub fn observers(&mut self) -> &mut Observers {
+        &mut self.observers
+    }
+
     pub fn run(&mut self) -> Result<(), String> {
-        let mut framecounter = 0;
+        let mut frames_since_tick = TICK_INTERVAL;
         let mut inputs = VecDeque::new();
+        // Direction key currently held down, if any
+        let mut held_dir = None;
+        // Where the current swipe on a touch screen started, if a finger is down
+        let mut swipe_start = None;
+        self.update_title()?;
         'mainloop: loop {
-            for event in self.event_pump.poll_iter() {
+            // Being interrupted or terminated leaves the loop like quitting does, so the game is
+            // still saved
+            if signal::shutdown_requested() {
+                break 'mainloop;
+            }
+            let events: Vec<Event> = self.event_pump.poll_iter().collect();
+            for event in events {
                 match event {
                     Event::Quit { .. } => break 'mainloop,
                     Event::KeyDown {
@@ -45,151 +137,1431 @@ impl Engine {
                                         self.mouse.show_cursor(true);
                                     }
                                 }
-                                let (level_width, level_height) = self.game_state.level_size();
+                                let (width, height) = self.logical_size();
                                 self.renderer
-                                    .set_logical_size(level_width as u32 * self.tile_size,
-                                                      level_height as u32 * self.tile_size)
+                                    .set_logical_size(width, height)
                                     .or_else(|e| Err(format!("{}", e)))?;
                             }
+                            _ if self.replay_player.is_some() => self.control_replay(keycode)?,
                             Keycode::W => {
                                 self.game_state.toggle_walls();
+                                // Changes outside of GameState::update can't be replayed
+                                self.replay = Replay::new(&self.game_state);
+                            }
+                            Keycode::E => {
+                                let mut rules = self.game_state.rules().clone();
+                                rules.edge_behavior = match rules.edge_behavior {
+                                    EdgeBehavior::Wrap => EdgeBehavior::WarnThenWrap,
+                                    EdgeBehavior::WarnThenWrap => EdgeBehavior::Wrap,
+                                };
+                                self.game_state.set_rules(rules);
+                                self.replay = Replay::new(&self.game_state);
+                            }
+                            Keycode::T => self.turbo = !self.turbo,
+                            Keycode::F1 => self.settings.hud.visible = !self.settings.hud.visible,
+                            Keycode::P => self.settings.progress_bar = !self.settings.progress_bar,
+                            Keycode::K => self.settings.screen_shake = !self.settings.screen_shake,
+                            Keycode::C => self.settings.patterns = !self.settings.patterns,
+                            Keycode::M => {
+                                self.settings.smooth_movement = !self.settings.smooth_movement;
+                            }
+                            Keycode::N => {
+                                self.settings.wrap_animation = !self.settings.wrap_animation;
+                            }
+                            Keycode::L => self.settings.edge_glow = !self.settings.edge_glow,
+                            Keycode::Y => self.settings.graveyard = !self.settings.graveyard,
+                            Keycode::G if self.debug => {
+                                self.show_coordinates = !self.show_coordinates;
                             }
-                            Keycode::Up => inputs.push_back(Direction::Up),
-                            Keycode::Down => inputs.push_back(Direction::Down),
-                            Keycode::Left => inputs.push_back(Direction::Left),
-                            Keycode::Right => inputs.push_back(Direction::Right),
-                            Keycode::Return => {
-                                if !self.game_state.snake_alive() {
-                                    self.game_state.reset();
+                            Keycode::H if self.debug => {
+                                self.show_reachable = !self.show_reachable;
+                            }
+                            Keycode::B if self.debug => {
+                                self.show_bot_scores = !self.show_bot_scores;
+                            }
+                            Keycode::R => {
+                                if self.game_state.game_over() && !self.autopilot {
+                                    self.replay_player =
+                                        Some(ReplayPlayer::new(self.replay.clone()));
                                 }
                             }
-                            _ => {}
+                            Keycode::Return => self.restart()?,
+                            _ => {
+                                if let Some(dir) = key_direction(keycode) {
+                                    self.queue_input(&mut inputs, dir);
+                                    held_dir = Some(dir);
+                                }
+                            }
+                        }
+                    }
+                    Event::KeyUp { keycode: Some(keycode), .. } => {
+                        // Releasing the held direction key disengages turbo
+                        held_dir = held_dir.filter(|&dir| key_direction(keycode) != Some(dir));
+                    }
+                    Event::ControllerDeviceAdded { which, .. } if self.controller.is_none() => {
+                        // The event has the device index rather than the instance id
+                        self.controller = controller::open_if_chosen(&self.controller_subsystem,
+                                                                     &self.settings.input_device,
+                                                                     which as u32);
+                    }
+                    Event::ControllerDeviceRemoved { which, .. } if
+                        self.is_controller(which) => {
+                        println!("Controller disconnected, falling back to the keyboard");
+                        self.controller = None;
+                    }
+                    Event::ControllerButtonDown { which, button, .. } if
+                        self.is_controller(which) && self.replay_player.is_none() => {
+                        if button == Button::Start {
+                            self.restart()?;
+                        } else if let Some(dir) = controller::button_direction(button) {
+                            self.queue_input(&mut inputs, dir);
+                            held_dir = Some(dir);
+                        }
+                    }
+                    Event::ControllerButtonUp { which, button, .. } if
+                        self.is_controller(which) => {
+                        held_dir = held_dir.filter(|&dir| {
+                            controller::button_direction(button) != Some(dir)
+                        });
+                    }
+                    Event::FingerDown { x, y, .. } => swipe_start = Some((x, y)),
+                    Event::FingerMotion { x, y, .. } | Event::FingerUp { x, y, .. } => {
+                        let swipe = swipe_start.filter(|_| self.replay_player.is_none())
+                            .map(|(start_x, start_y)| (x - start_x, y - start_y));
+                        if let Some(swipe) = swipe {
+                            let dir = input::swipe_direction(swipe, self.settings.swipe_distance);
+                            if let Some(dir) = dir {
+                                self.queue_input(&mut inputs, dir);
+                                // Keep steering with the same finger by measuring the next
+                                // swipe from here
+                                swipe_start = Some((x, y));
+                            }
+                        }
+                        if let Event::FingerUp { .. } = event {
+                            swipe_start = None;
                         }
                     }
                     _ => {}
                 }
             }
-            if framecounter % 10 == 0 {
-                self.game_state.update(inputs.pop_front())?;
+            let tick_interval = if self.autopilot {
+                TICK_INTERVAL
+            } else {
+                // The held key turns the snake the way the chaos mode currently maps it to
+                let game_time = self.game_time();
+                let held_dir = held_dir.map(|dir| steer(self.chaos.as_ref(), game_time, dir));
+                tick_interval(self.turbo,
+                              held_dir,
+                              self.game_state.snake_dir(),
+                              !inputs.is_empty())
+            };
+            if frames_since_tick >= tick_interval {
+                frames_since_tick = 0;
+                self.tick_rate.count();
+                if let Some(ref mut player) = self.replay_player {
+                    if !player.paused() {
+                        player.step_forward()?;
+                    }
+                } else if self.autopilot {
+                    let boards = ::std::iter::once(&mut self.game_state)
+                        .chain(self.boards.iter_mut());
+                    for game_state in boards {
+                        if game_state.game_over() {
+                            game_state.reset();
+                        }
+                        let input = game_state.suggest_direction();
+                        game_state.update(Some(input))?;
+                    }
+                } else {
+                    let (input, remaining) = if self.game_state.started() {
+                        input::resolve_input(inputs, self.game_state.snake_dir())
+                    } else {
+                        input::resolve_start(inputs, self.game_state.snake_dir())
+                    };
+                    inputs = remaining;
+                    let highscore = self.game_state.highscore();
+                    // Ticks after the game ended change nothing, so they aren't part of the replay
+                    let running = !self.game_state.game_over();
+                    let tick = self.game_state.tick();
+                    let events = self.game_state.update(input)?;
+                    if self.game_start.is_none() && self.game_state.started() {
+                        self.game_start = Some(Instant::now());
+                    }
+                    self.announce(&events, highscore);
+                    if running {
+                        self.replay.record(input);
+                    }
+                    if let Some(ref mut trace) = self.trace {
+                        trace.record(&self.game_state, input, tick)?;
+                    }
+                    self.observers.notify(&events);
+                    self.shake_for(&events);
+                    self.bury(&events);
+                }
+                self.update_title()?;
+            }
+            self.render(frames_since_tick as f32 / tick_interval as f32)?;
+            frames_since_tick += 1;
+            if self.frame_rate.count() && self.debug {
+                self.update_title()?;
             }
-            self.render()?;
-            framecounter += 1;
         }
 
-        // Save game state on exit
-        if !self.game_state.snake_alive() {
-            self.game_state.reset();
+        if let Some(ref mut trace) = self.trace {
+            trace.flush()?;
         }
-        self.game_state
-            .save(&::APP_INFO, "game_state")
-            .or_else(|e| Err(format!("{}", e)))?;
+        self.save()
+    }
+
+    // Queue a direction input from the player for the coming ticks
+    fn queue_input(&self, inputs: &mut VecDeque<Direction>, dir: Direction) {
+        let dir = steer(self.chaos.as_ref(), self.game_time(), dir);
+        let limit = self.settings.input_queue;
+        if self.settings.chain_turns && !self.game_state.started() {
+            input::queue_start(inputs, dir, self.game_state.snake_dir(), limit);
+        } else if self.settings.chain_turns {
+            input::queue_turn(inputs, dir, self.game_state.snake_dir(), limit);
+        } else {
+            input::push_bounded(inputs, dir, limit);
+        }
+    }
 
+    // How long the snake of the current game has been moving for, which is nothing while it is
+    // still waiting to start
+    fn game_time(&self) -> Duration {
+        self.game_start.map_or(Duration::from_secs(0), |start| start.elapsed())
+    }
+
+    // Start over once the game has ended
+    fn restart(&mut self) -> Result<(), String> {
+        if self.game_state.game_over() {
+            self.reset_game()?;
+            self.replay = Replay::new(&self.game_state);
+        }
+        Ok(())
+    }
+
+    // Whether the controller with the given instance id is the one used for steering
+    fn is_controller(&self, instance_id: i32) -> bool {
+        self.controller.as_ref().is_some_and(|controller| controller.instance_id() == instance_id)
+    }
+
+    // Start a new game, which may be on a different level with a different highscore
+    fn reset_game(&mut self) -> Result<(), String> {
+        self.highscores.set_highscore_for(&self.game_state.level_id(), self.game_state.highscore());
+        self.game_state.reset();
+        let highscore = self.highscores.highscore_for(&self.game_state.level_id());
+        self.game_state.set_highscore(highscore);
+        self.game_start = None;
+        if let Some(ref mut trace) = self.trace {
+            trace.record_reset(&self.game_state)?;
+        }
+        Ok(())
+    }
+
+    // Save settings, the highscores and the game state on exit
+    fn save(&mut self) -> Result<(), String> {
+        if !self.autopilot {
+            self.highscores
+                .set_highscore_for(&self.game_state.level_id(), self.game_state.highscore());
+            if self.auto_save && self.game_state.game_over() {
+                self.reset_game()?;
+            }
+        }
+        save_in(&storage::dir(&::APP_INFO)?,
+                &self.settings,
+                &self.highscores,
+                &self.graveyard,
+                &self.game_state,
+                self.autopilot,
+                self.auto_save)
+    }
+
+    fn control_replay(&mut self, keycode: Keycode) -> Result<(), String> {
+        if keycode == Keycode::R || keycode == Keycode::Return {
+            // Leave the replay and return to the finished game
+            self.replay_player = None;
+            return Ok(());
+        }
+        let player = self.replay_player.as_mut().unwrap();
+        let len = player.len();
+        match keycode {
+            Keycode::Space => player.toggle_pause(),
+            Keycode::Right | Keycode::Left => {
+                if !player.paused() {
+                    player.toggle_pause();
+                }
+                if keycode == Keycode::Right {
+                    player.step_forward()?;
+                } else {
+                    player.step_back()?;
+                }
+            }
+            Keycode::Home => player.seek(0)?,
+            Keycode::End => player.seek(len)?,
+            _ => {
+                // Number keys jump to the corresponding tenth of the replay
+                let digits = [Keycode::Num0, Keycode::Num1, Keycode::Num2, Keycode::Num3,
+                              Keycode::Num4, Keycode::Num5, Keycode::Num6, Keycode::Num7,
+                              Keycode::Num8, Keycode::Num9];
+                if let Some(digit) = digits.iter().position(|&digit| digit == keycode) {
+                    player.seek(len * digit / 10)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn update_title(&mut self) -> Result<(), String> {
+        let mut title = format!("Snake \u{2014} Score {}, Len {}",
+                                self.game_state.score(),
+                                self.game_state.snake_length());
+        if self.debug {
+            if let (Some(tps), Some(fps)) = (self.tick_rate.rate(), self.frame_rate.rate()) {
+                title.push_str(&format!(" \u{2014} {:.1} TPS, {:.0} FPS", tps, fps));
+            }
+        }
+        if self.title != title {
+            self.renderer
+                .window_mut()
+                .unwrap()
+                .set_title(&title)
+                .map_err(|e| format!("{}", e))?;
+            self.title = title;
+        }
         Ok(())
     }
 
-    fn render(&mut self) -> Result<(), String> {
-        // Clear surface to black
-        self.renderer.set_draw_color(Color::RGB(0, 0, 0));
+    // Print the outcome of the player's game once it ends, given the highscore from before
+    fn announce(&self, events: &[GameEvent], highscore: u32) {
+        for event in events {
+            let message = match *event {
+                GameEvent::Died { .. } => "Game over!",
+                GameEvent::Won => "You win!",
+                GameEvent::TimeUp => "Time's up!",
+                _ => continue,
+            };
+            let score = self.game_state.score();
+            println!("{}", message);
+            println!("Your score: {}", score);
+            if score > highscore {
+                println!("*** New highscore! ***");
+            } else {
+                println!("Highscore: {}", highscore);
+            }
+        }
+    }
+
+    // Start shaking the screen for events that deserve it, unless shaking is turned off
+    fn shake_for(&mut self, events: &[GameEvent]) {
+        if !self.settings.screen_shake {
+            return;
+        }
+        for event in events {
+            let intensity = match *event {
+                GameEvent::Died { .. } => DEATH_SHAKE,
+                GameEvent::BrokeWall { .. } => WALL_BREAK_SHAKE,
+                _ => continue,
+            };
+            // A weaker shake doesn't cut a stronger one short
+            if intensity >= shake_amplitude(self.shake_frames, self.shake_intensity) {
+                self.shake_frames = SHAKE_FRAMES;
+                self.shake_intensity = intensity;
+            }
+        }
+    }
+
+    // Record where the player's snake died in the graveyard
+    fn bury(&mut self, events: &[GameEvent]) {
+        for event in events {
+            if let GameEvent::Died { index } = *event {
+                self.graveyard.record_death(&self.game_state.level_id(),
+                                            index,
+                                            self.settings.graveyard_size);
+            }
+        }
+    }
+
+    // Render all boards, given how far along the current tick is from 0 to 1
+    fn render(&mut self, tick_progress: f32) -> Result<(), String> {
+        // Clear surface to the background color
+        self.renderer.set_draw_color(self.settings.theme.background.color());
         self.renderer.clear();
 
-        // Draw floor
-        let floor_color = if self.game_state.snake_alive() {
-            Color::RGB(0, 0, 255)
+        // Draw each board into its own cell of the board grid
+        let (level_width, level_height) = self.game_state.level_size();
+        let (board_width, board_height) = (level_width as u32 * self.tile_size,
+                                           level_height as u32 * self.tile_size);
+        let (columns, _) = board_grid(self.boards.len() + 1);
+        let main_board = match self.replay_player {
+            Some(ref player) => player.game_state(),
+            None => &self.game_state,
+        };
+        let boards = ::std::iter::once(main_board).chain(self.boards.iter());
+        // Only the main board shakes, since that is where the events come from
+        let amplitude = shake_amplitude(self.shake_frames, self.shake_intensity) as i32;
+        let shake = if amplitude > 0 {
+            let mut rng = rand::thread_rng();
+            (rng.gen_range(-amplitude, amplitude + 1), rng.gen_range(-amplitude, amplitude + 1))
         } else {
-            Color::RGB(128, 0, 0)
+            (0, 0)
         };
-        self.renderer.set_draw_color(floor_color);
-        let (level_width, level_height) = self.game_state.level_size();
-        self.renderer
-            .fill_rect(Rect::new(0,
-                                 0,
-                                 level_width as u32 * self.tile_size,
-                                 level_height as u32 * self.tile_size))?;
-
-        // Draw tiles other than floor
-        for ((y, x), &tile) in self.game_state.tiles().indexed_iter() {
-            match tile {
-                Tile::Floor => {}
-                Tile::Wall => {
-                    self.renderer.set_draw_color(Color::RGB(255, 0, 0));
-                    self.renderer
-                        .fill_rect(Rect::new(x as i32 * self.tile_size as i32,
-                                             y as i32 * self.tile_size as i32,
-                                             self.tile_size,
-                                             self.tile_size))?;
-                }
-                Tile::Food => {
-                    self.renderer.set_draw_color(Color::RGB(255, 255, 0));
-                    self.renderer
-                        .fill_rect(Rect::new(x as i32 * self.tile_size as i32 + 1,
-                                             y as i32 * self.tile_size as i32 + 1,
-                                             self.tile_size - 2,
-                                             self.tile_size - 2))?;
+        self.shake_frames = self.shake_frames.saturating_sub(1);
+        for (i, game_state) in boards.enumerate() {
+            let (column, row) = ((i % columns) as u32, (i / columns) as u32);
+            let (offset_x, offset_y) = if i == 0 { shake } else { (0, 0) };
+            self.renderer
+                .set_viewport(Some(Rect::new((column * board_width) as i32 + offset_x,
+                                             (row * board_height) as i32 + offset_y,
+                                             board_width,
+                                             board_height)));
+            render_board(&mut self.renderer,
+                         game_state,
+                         &self.settings,
+                         self.tile_size,
+                         tick_progress.min(1.0))?;
+            if self.settings.edge_glow {
+                render_edge_glow(&mut self.renderer,
+                                 game_state,
+                                 &self.settings,
+                                 self.tile_size,
+                                 edge_glow_intensity(self.frame))?;
+            }
+            if i == 0 && self.settings.graveyard {
+                let graves = self.graveyard.deaths_on(&game_state.level_id());
+                render_graves(&mut self.renderer, graves, &self.settings, self.tile_size)?;
+            }
+            if self.show_reachable {
+                render_reachable(&mut self.renderer, game_state, &self.settings, self.tile_size)?;
+            }
+            if self.show_bot_scores && self.autopilot && !game_state.game_over() {
+                render_bot_scores(&mut self.renderer, game_state, &self.settings, self.tile_size)?;
+            }
+            if self.show_coordinates {
+                render_coordinates(&mut self.renderer, game_state, &self.settings, self.tile_size)?;
+            }
+            if i == 0 {
+                hud::draw(&mut self.renderer,
+                          &self.settings,
+                          game_state,
+                          (board_width, board_height))?;
+                let warning = self.chaos
+                    .as_ref()
+                    .and_then(|chaos| chaos.warning_at(self.game_time()));
+                if let Some(warning) = warning.filter(|_| !game_state.game_over()) {
+                    render_warning(&mut self.renderer,
+                                   &self.settings,
+                                   warning,
+                                   (board_width, board_height))?;
                 }
-                Tile::Snake(prev, next) => {
-                    self.renderer.set_draw_color(Color::RGB(0, 255, 0));
-                    if prev == Some(Direction::Up) || next == Some(Direction::Up) {
-                        self.renderer
-                            .fill_rect(Rect::new(x as i32 * self.tile_size as i32 + 1,
-                                                 y as i32 * self.tile_size as i32,
-                                                 self.tile_size - 2,
-                                                 self.tile_size - 1))?;
-                    }
-                    if prev == Some(Direction::Down) || next == Some(Direction::Down) {
-                        self.renderer
-                            .fill_rect(Rect::new(x as i32 * self.tile_size as i32 + 1,
-                                                 y as i32 * self.tile_size as i32 + 1,
-                                                 self.tile_size - 2,
-                                                 self.tile_size - 1))?;
-                    }
-                    if prev == Some(Direction::Left) || next == Some(Direction::Left) {
-                        self.renderer
-                            .fill_rect(Rect::new(x as i32 * self.tile_size as i32,
-                                                 y as i32 * self.tile_size as i32 + 1,
-                                                 self.tile_size - 1,
-                                                 self.tile_size - 2))?;
-                    }
-                    if prev == Some(Direction::Right) || next == Some(Direction::Right) {
-                        self.renderer
-                            .fill_rect(Rect::new(x as i32 * self.tile_size as i32 + 1,
-                                                 y as i32 * self.tile_size as i32 + 1,
-                                                 self.tile_size - 1,
-                                                 self.tile_size - 2))?;
-                    }
+                if self.settings.summary && self.replay_player.is_none() &&
+                   game_state.game_over() {
+                    summary::draw(&mut self.renderer,
+                                  &self.settings,
+                                  game_state,
+                                  (board_width, board_height))?;
                 }
             }
         }
+        self.renderer.set_viewport(None);
 
         // Present surface to screen
         self.renderer.present();
+        self.frame = self.frame.wrapping_add(1);
 
         Ok(())
     }
+
+    // Logical size of the whole window, covering all boards
+    fn logical_size(&self) -> (u32, u32) {
+        let (level_width, level_height) = self.game_state.level_size();
+        let (columns, rows) = board_grid(self.boards.len() + 1);
+        (columns as u32 * level_width as u32 * self.tile_size,
+         rows as u32 * level_height as u32 * self.tile_size)
+    }
+}
+
+fn key_direction(keycode: Keycode) -> Option<Direction> {
+    match keycode {
+        Keycode::Up => Some(Direction::Up),
+        Keycode::Down => Some(Direction::Down),
+        Keycode::Left => Some(Direction::Left),
+        Keycode::Right => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+// Save everything kept between runs in the given directory.  Nothing but the settings is saved if
+// the game was only being played by the autopilot, and the game state is only saved with
+// auto-save enabled.
+fn save_in(dir: &Path,
+           settings: &Settings,
+           highscores: &Highscores,
+           graveyard: &Graveyard,
+           game_state: &GameState,
+           autopilot: bool,
+           auto_save: bool)
+           -> Result<(), String> {
+    storage::save_in(dir, "settings", settings)?;
+    if autopilot {
+        return Ok(());
+    }
+    storage::save_in(dir, "highscores", highscores)?;
+    storage::save_in(dir, "graveyard", graveyard)?;
+    if auto_save {
+        storage::save_in(dir, "game_state", game_state)?;
+    }
+    Ok(())
+}
+
+// Direction the snake is steered in for the given input, which the chaos mode remaps depending
+// on how long the snake has been moving for
+fn steer(chaos: Option<&ChaosControls>, game_time: Duration, dir: Direction) -> Direction {
+    match chaos {
+        Some(chaos) => chaos.transform_at(game_time).apply(dir),
+        None => dir,
+    }
+}
+
+// Number of frames until the next game tick.  Turbo only applies while the key for the direction
+// the snake is already heading in is held and no turns are waiting to be applied, so turns always
+// happen at normal speed.
+fn tick_interval(turbo: bool,
+                 held_dir: Option<Direction>,
+                 snake_dir: Direction,
+                 turn_pending: bool)
+                 -> u32 {
+    if turbo && held_dir == Some(snake_dir) && !turn_pending {
+        TURBO_TICK_INTERVAL
+    } else {
+        TICK_INTERVAL
+    }
+}
+
+// Largest offset in pixels of a screen shake with the given number of frames left, fading out
+// linearly from the given intensity to nothing over the course of the shake
+fn shake_amplitude(frames_left: u32, intensity: u32) -> u32 {
+    (intensity * frames_left.min(SHAKE_FRAMES)).div_ceil(SHAKE_FRAMES)
+}
+
+// Size of the window showing the given logical size at the given render scale
+fn scaled_size((width, height): (u32, u32), scale: f32) -> (u32, u32) {
+    ((width as f32 * scale).round() as u32, (height as f32 * scale).round() as u32)
+}
+
+// Number of columns and rows of the most square grid holding the given number of boards
+fn board_grid(boards: usize) -> (usize, usize) {
+    let mut columns = 1;
+    while columns * columns < boards {
+        columns += 1;
+    }
+    (columns, boards.div_ceil(columns))
+}
+
+fn render_board(renderer: &mut sdl2::render::Renderer,
+                game_state: &GameState,
+                settings: &Settings,
+                tile_size: u32,
+                tick_progress: f32)
+                -> Result<(), String> {
+    let theme = &settings.theme;
+    let smooth_head = settings.smooth_movement && game_state.snake_moving();
+
+    // Draw floor
+    let floor_color = if game_state.won() {
+        theme.won_floor.color()
+    } else if game_state.snake_alive() && !game_state.timed_out() {
+        theme.floor.color()
+    } else {
+        theme.dead_floor.color()
+    };
+    renderer.set_draw_color(floor_color);
+    let (level_width, level_height) = game_state.level_size();
+    renderer.fill_rect(Rect::new(0,
+                                 0,
+                                 level_width as u32 * tile_size,
+                                 level_height as u32 * tile_size))?;
+
+    // Draw painted floor
+    if game_state.rules().paint_goal > 0 {
+        renderer.set_draw_color(theme.painted_floor.color());
+        for ((y, x), &painted) in game_state.painted().indexed_iter() {
+            if painted {
+                renderer.fill_rect(Rect::new(x as i32 * tile_size as i32,
+                                             y as i32 * tile_size as i32,
+                                             tile_size,
+                                             tile_size))?;
+            }
+        }
+    }
+
+    // Draw ink zones
+    for ((y, x), &zone) in game_state.zones().indexed_iter() {
+        let zone_color = match zone {
+            Zone::Plain => continue,
+            Zone::Uninked => theme.uninked_zone,
+            Zone::Inked => theme.inked_zone,
+        };
+        renderer.set_draw_color(zone_color.color());
+        renderer.fill_rect(Rect::new(x as i32 * tile_size as i32,
+                                     y as i32 * tile_size as i32,
+                                     tile_size,
+                                     tile_size))?;
+    }
+
+    // Draw ghost walls, faintly while phased out and half see-through in the last few ticks
+    // before changing over
+    renderer.set_blend_mode(BlendMode::Blend);
+    let changing = game_state.ticks_until_ghost_phase_change()
+        .is_some_and(|ticks| ticks <= GHOST_WALL_TRANSITION);
+    let ghost_alpha = match (game_state.ghost_walls_solid(), changing) {
+        (true, false) => 255,
+        (true, true) | (false, true) => 128,
+        (false, false) => 48,
+    };
+    renderer.set_draw_color(theme.wall.with_alpha(ghost_alpha));
+    for ((y, x), &ghost_wall) in game_state.ghost_walls().indexed_iter() {
+        if ghost_wall {
+            renderer.fill_rect(Rect::new(x as i32 * tile_size as i32,
+                                         y as i32 * tile_size as i32,
+                                         tile_size,
+                                         tile_size))?;
+        }
+    }
+    renderer.set_blend_mode(BlendMode::None);
+
+    // Draw tiles other than floor
+    for ((y, x), &tile) in game_state.tiles().indexed_iter() {
+        match tile {
+            Tile::Floor => {}
+            Tile::Wall => {
+                renderer.set_draw_color(theme.wall.color());
+                renderer.fill_rect(Rect::new(x as i32 * tile_size as i32,
+                                             y as i32 * tile_size as i32,
+                                             tile_size,
+                                             tile_size))?;
+            }
+            Tile::Food => {
+                renderer.set_draw_color(theme.food.color());
+                renderer.fill_rect(Rect::new(x as i32 * tile_size as i32 + 1,
+                                             y as i32 * tile_size as i32 + 1,
+                                             tile_size - 2,
+                                             tile_size - 2))?;
+            }
+            Tile::ChainFood(order) => {
+                // Number the food with its place in the chain
+                let (left, top) = (x as i32 * tile_size as i32, y as i32 * tile_size as i32);
+                renderer.set_draw_color(theme.food.color());
+                renderer.fill_rect(Rect::new(left + 1, top + 1, tile_size - 2, tile_size - 2))?;
+                let label = order.to_string();
+                let (width, height) = text::text_size(&label, 1);
+                renderer.set_draw_color(theme.background.color());
+                text::draw_text(renderer,
+                                &label,
+                                left + (tile_size as i32 - width as i32) / 2,
+                                top + (tile_size as i32 - height as i32) / 2,
+                                1)?;
+            }
+            Tile::BigFood { top, left } => {
+                // Only inset the outer edges of the block, so the four tiles join into one
+                renderer.set_draw_color(theme.food.color());
+                renderer.fill_rect(Rect::new(x as i32 * tile_size as i32 + left as i32,
+                                             y as i32 * tile_size as i32 + top as i32,
+                                             tile_size - 1,
+                                             tile_size - 1))?;
+            }
+            Tile::WallBreaker => {
+                renderer.set_draw_color(theme.wall_breaker.color());
+                renderer.fill_rect(Rect::new(x as i32 * tile_size as i32 + 2,
+                                             y as i32 * tile_size as i32 + 2,
+                                             tile_size - 4,
+                                             tile_size - 4))?;
+            }
+            Tile::Snake(..) if smooth_head && (y, x) == game_state.snake_head() => {
+                // Drawn separately below
+            }
+            Tile::Snake(prev, next) => {
+                let snake_color = match game_state.snake_part_at((y, x)) {
+                    Some(SnakePart::Head) => theme.snake_head,
+                    _ => theme.snake,
+                };
+                renderer.set_draw_color(snake_color.color());
+                for &dir in &DIRECTIONS {
+                    if prev == Some(dir) || next == Some(dir) {
+                        renderer.fill_rect(snake_rect((y, x), dir, tile_size))?;
+                    }
+                }
+            }
+        }
+    }
+
+    // Cut patterns into tiles so they can be told apart by shape as well as by color
+    if settings.patterns {
+        renderer.set_draw_color(theme.background.color());
+        for (index, &tile) in game_state.tiles().indexed_iter() {
+            for rect in pattern_rects(index, tile_pattern(tile), tile_size) {
+                renderer.fill_rect(rect)?;
+            }
+        }
+    }
+
+    if smooth_head {
+        render_smooth_head(renderer, game_state, settings, tile_size, tick_progress)?;
+    }
+
+    // Draw one pip per wall break charge along the bottom left of the board
+    renderer.set_draw_color(theme.wall_breaker.color());
+    for i in 0..game_state.wall_break_charges() {
+        renderer.fill_rect(Rect::new(2 + i as i32 * 4,
+                                     (level_height as u32 * tile_size) as i32 - 5,
+                                     3,
+                                     3))?;
+    }
+
+    // Draw a bar along the bottom edge showing progress towards the length needed to win
+    if settings.progress_bar {
+        let (width, height) = (level_width as u32 * tile_size, level_height as u32 * tile_size);
+        renderer.set_draw_color(theme.progress_bar_background.color());
+        renderer.fill_rect(Rect::new(0, height as i32 - 2, width, 2))?;
+        let filled = (width as f32 * game_state.win_progress()) as u32;
+        if filled > 0 {
+            renderer.set_draw_color(theme.progress_bar.color());
+            renderer.fill_rect(Rect::new(0, height as i32 - 2, filled, 2))?;
+        }
+    }
+
+    Ok(())
 }
 
-pub fn init() -> Result<Engine, String> {
-    let game_state = GameState::load(&::APP_INFO, "game_state").unwrap_or_default();
+// Number of ticks before ghost walls change over during which they are drawn half see-through
+const GHOST_WALL_TRANSITION: u32 = 3;
+
+// Number of frames the edge glow takes to pulse once, and its opacity at its brightest
+const EDGE_GLOW_PERIOD: u32 = 90;
+const EDGE_GLOW_ALPHA: u8 = 160;
+
+// Brightness of the edge glow in the given frame, from 0 to 1, pulsing smoothly from dark to
+// bright and back once every EDGE_GLOW_PERIOD frames
+fn edge_glow_intensity(frame: u32) -> f32 {
+    let phase = (frame % EDGE_GLOW_PERIOD) as f32 / EDGE_GLOW_PERIOD as f32;
+    0.5 - 0.5 * (phase * 2.0 * ::std::f32::consts::PI).cos()
+}
+
+// Draw a glow fading inwards from the level edges wherever the snake can wrap around, which is
+// wherever neither the edge tile nor the one on the opposite edge is a wall
+fn render_edge_glow(renderer: &mut sdl2::render::Renderer,
+                    game_state: &GameState,
+                    settings: &Settings,
+                    tile_size: u32,
+                    intensity: f32)
+                    -> Result<(), String> {
+    let tiles = game_state.tiles();
+    let (level_width, level_height) = game_state.level_size();
+    let (board_width, board_height) = (level_width as i32 * tile_size as i32,
+                                       level_height as i32 * tile_size as i32);
+    let open = |a: TileIndex, b: TileIndex| tiles[a] != Tile::Wall && tiles[b] != Tile::Wall;
+    let band = (tile_size / 2).max(1);
+    renderer.set_blend_mode(BlendMode::Blend);
+    for i in 0..band {
+        let fade = 1.0 - i as f32 / band as f32;
+        let alpha = (EDGE_GLOW_ALPHA as f32 * intensity * fade) as u8;
+        renderer.set_draw_color(settings.theme.edge_glow.with_alpha(alpha));
+        let i = i as i32;
+        for x in 0..level_width {
+            if open((0, x), (level_height - 1, x)) {
+                let left = x as i32 * tile_size as i32;
+                renderer.fill_rect(Rect::new(left, i, tile_size, 1))?;
+                renderer.fill_rect(Rect::new(left, board_height - 1 - i, tile_size, 1))?;
+            }
+        }
+        for y in 0..level_height {
+            if open((y, 0), (y, level_width - 1)) {
+                let top = y as i32 * tile_size as i32;
+                renderer.fill_rect(Rect::new(i, top, 1, tile_size))?;
+                renderer.fill_rect(Rect::new(board_width - 1 - i, top, 1, tile_size))?;
+            }
+        }
+    }
+    renderer.set_blend_mode(BlendMode::None);
+    Ok(())
+}
+
+// Opacity of the markers where past snakes died
+const GRAVE_ALPHA: u8 = 64;
+
+// Faintly mark the given tiles where past snakes died with a small square each
+fn render_graves(renderer: &mut sdl2::render::Renderer,
+                 graves: &[TileIndex],
+                 settings: &Settings,
+                 tile_size: u32)
+                 -> Result<(), String> {
+    let inset = tile_size / 4;
+    renderer.set_blend_mode(BlendMode::Blend);
+    renderer.set_draw_color(settings.theme.graves.with_alpha(GRAVE_ALPHA));
+    for &(y, x) in graves {
+        renderer.fill_rect(Rect::new((x as u32 * tile_size + inset) as i32,
+                                     (y as u32 * tile_size + inset) as i32,
+                                     tile_size - 2 * inset,
+                                     tile_size - 2 * inset))?;
+    }
+    renderer.set_blend_mode(BlendMode::None);
+    Ok(())
+}
+
+// Opacity of the reachable floor overlay
+const REACHABLE_ALPHA: u8 = 96;
+
+// Tint floor tiles by whether the snake's head can reach them
+fn render_reachable(renderer: &mut sdl2::render::Renderer,
+                    game_state: &GameState,
+                    settings: &Settings,
+                    tile_size: u32)
+                    -> Result<(), String> {
+    let reachable = game_state.reachable_from_head();
+    renderer.set_blend_mode(BlendMode::Blend);
+    for ((y, x), &tile) in game_state.tiles().indexed_iter() {
+        if tile != Tile::Floor {
+            continue;
+        }
+        let tint = if reachable[(y, x)] {
+            settings.theme.reachable
+        } else {
+            settings.theme.unreachable
+        };
+        renderer.set_draw_color(tint.with_alpha(REACHABLE_ALPHA));
+        renderer.fill_rect(Rect::new(x as i32 * tile_size as i32,
+                                     y as i32 * tile_size as i32,
+                                     tile_size,
+                                     tile_size))?;
+    }
+    renderer.set_blend_mode(BlendMode::None);
+    Ok(())
+}
+
+// Write the autopilot's score for each direction onto the tile next to the snake's head that the
+// direction leads to
+fn render_bot_scores(renderer: &mut sdl2::render::Renderer,
+                     game_state: &GameState,
+                     settings: &Settings,
+                     tile_size: u32)
+                     -> Result<(), String> {
+    renderer.set_draw_color(settings.theme.bot_scores.color());
+    for (&dir, score) in &game_state.suggest_direction_detailed() {
+        let (y, x) = game_state.add_dir_to_index(game_state.snake_head(), dir);
+        text::draw_text(renderer,
+                        &score.to_string(),
+                        (x as u32 * tile_size) as i32 + 1,
+                        (y as u32 * tile_size) as i32 + 1,
+                        1)?;
+    }
+    Ok(())
+}
+
+// Label the columns along the top edge of the board and the rows along its left edge, skipping
+// labels where they would be wider than the tiles they label
+fn render_coordinates(renderer: &mut sdl2::render::Renderer,
+                      game_state: &GameState,
+                      settings: &Settings,
+                      tile_size: u32)
+                      -> Result<(), String> {
+    let (level_width, level_height) = game_state.level_size();
+    renderer.set_draw_color(settings.theme.coordinates.color());
+    let label_width = text::text_size(&level_width.max(level_height).to_string(), 1).0 + 1;
+    let step = label_width.div_ceil(tile_size) as usize;
+    for x in (0..level_width).step_by(step) {
+        text::draw_text(renderer, &x.to_string(), (x as u32 * tile_size) as i32 + 1, 1, 1)?;
+    }
+    // The first row's label would overlap the first column's
+    for y in (step..level_height).step_by(step) {
+        text::draw_text(renderer, &y.to_string(), 1, (y as u32 * tile_size) as i32 + 1, 1)?;
+    }
+    Ok(())
+}
+
+// Draw a warning across the upper part of a board of the given size
+fn render_warning(renderer: &mut sdl2::render::Renderer,
+                  settings: &Settings,
+                  warning: &str,
+                  (board_width, board_height): (u32, u32))
+                  -> Result<(), String> {
+    let width = text::text_size(warning, 1).0;
+    renderer.set_draw_color(settings.theme.text.color());
+    text::draw_text(renderer,
+                    warning,
+                    (board_width as i32 - width as i32) / 2,
+                    (board_height / 4) as i32,
+                    1)
+}
+
+// Shape cut into a tile in pattern mode
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Pattern {
+    None,
+    // Lines through the middle of the tile, which join into a grid across neighbouring tiles
+    Cross,
+    // A dot in the middle of the tile
+    Dot,
+    // A horizontal line through the middle of the tile
+    Bar,
+}
+
+fn tile_pattern(tile: Tile) -> Pattern {
+    match tile {
+        Tile::Wall => Pattern::Cross,
+        Tile::Food | Tile::BigFood { .. } | Tile::ChainFood(_) => Pattern::Dot,
+        Tile::WallBreaker => Pattern::Bar,
+        Tile::Floor | Tile::Snake(..) => Pattern::None,
+    }
+}
+
+fn pattern_rects((y, x): TileIndex, pattern: Pattern, tile_size: u32) -> Vec<Rect> {
+    let (x, y) = (x as i32 * tile_size as i32, y as i32 * tile_size as i32);
+    let middle = tile_size as i32 / 2;
+    match pattern {
+        Pattern::None => vec![],
+        Pattern::Cross => {
+            vec![Rect::new(x, y + middle, tile_size, 1), Rect::new(x + middle, y, 1, tile_size)]
+        }
+        Pattern::Dot => vec![Rect::new(x + middle - 1, y + middle - 1, 2, 2)],
+        Pattern::Bar => vec![Rect::new(x + 2, y + middle, tile_size - 4, 1)],
+    }
+}
+
+// Part of a snake tile joining it to the neighbouring segment in the given direction
+fn snake_rect((y, x): TileIndex, dir: Direction, tile_size: u32) -> Rect {
+    let (x, y) = (x as i32 * tile_size as i32, y as i32 * tile_size as i32);
+    match dir {
+        Direction::Up => Rect::new(x + 1, y, tile_size - 2, tile_size - 1),
+        Direction::Down => Rect::new(x + 1, y + 1, tile_size - 2, tile_size - 1),
+        Direction::Left => Rect::new(x, y + 1, tile_size - 1, tile_size - 2),
+        Direction::Right => Rect::new(x + 1, y + 1, tile_size - 1, tile_size - 2),
+    }
+}
+
+// Draw the snake's head part of the way from the previous tile to its current one.  When the
+// head has just wrapped around the level, it slides out past one edge while sliding in from the
+// opposite one, unless the wrap animation is off, in which case it stays put for the tick.
+fn render_smooth_head(renderer: &mut sdl2::render::Renderer,
+                      game_state: &GameState,
+                      settings: &Settings,
+                      tile_size: u32,
+                      tick_progress: f32)
+                      -> Result<(), String> {
+    let head = game_state.snake_head();
+    let prev = match game_state.tiles()[head] {
+        Tile::Snake(Some(prev), _) => prev,
+        _ => return Ok(()),
+    };
+    let dir = prev.reverse();
+    let rect = snake_rect(head, prev, tile_size);
+    let (level_width, level_height) = game_state.level_size();
+    let (board_width, board_height) = (level_width as i32 * tile_size as i32,
+                                       level_height as i32 * tile_size as i32);
+    let board = Rect::new(0, 0, board_width as u32, board_height as u32);
+    let wrapped = game_state.crosses_edge(game_state.add_dir_to_index(head, prev), dir);
+    let animate_wrap = settings.wrap_animation &&
+                       game_state.rules().edge_behavior == EdgeBehavior::Wrap;
+
+    renderer.set_draw_color(settings.theme.snake_head.color());
+    if wrapped && !animate_wrap {
+        return renderer.fill_rect(rect);
+    }
+    let (dx, dy) = match dir {
+        Direction::Up => (0, -1),
+        Direction::Down => (0, 1),
+        Direction::Left => (-1, 0),
+        Direction::Right => (1, 0),
+    };
+    let behind = ((1.0 - tick_progress) * tile_size as f32) as i32;
+    let mut entering = rect;
+    entering.offset(-dx * behind, -dy * behind);
+    let mut rects = vec![entering];
+    if wrapped {
+        // The part of the head still leaving through the edge it crossed
+        let mut leaving = entering;
+        leaving.offset(dx * board_width, dy * board_height);
+        rects.push(leaving);
+    }
+    for rect in rects.into_iter().filter_map(|rect| rect.intersection(board)) {
+        renderer.fill_rect(rect)?;
+    }
+    Ok(())
+}
+
+// Startup options, usually given on the command line
+pub struct Options {
+    // Number of boards to show; with more than one, all boards are played by the autopilot
+    pub boards: usize,
+    // Device to steer with, replacing the one stored in the settings
+    pub input_device: Option<InputDevice>,
+    // Print the connected controllers instead of starting a game
+    pub list_controllers: bool,
+    // Factor the window is scaled up (or down) by, without changing the size of the game grid
+    pub render_scale: f32,
+    // Enable debugging aids
+    pub debug: bool,
+    // Override for whether the snake waits for the first input before moving
+    pub start_mode: Option<StartMode>,
+    // Overrides for the food decay rules
+    pub food_decay: Option<u32>,
+    pub food_value_floor: Option<u32>,
+    // Overrides for the zone around the snake's head that food doesn't spawn in
+    pub food_protection_radius: Option<usize>,
+    pub food_protection_ahead: Option<usize>,
+    // Override for the percentage of the level the snake has to fill to win
+    pub win_fill_percent: Option<u32>,
+    // Override for the number of ink zones in a new level
+    pub ink_zones: Option<usize>,
+    // Override for the percentage of the level the snake has to paint to win
+    pub paint_goal: Option<u32>,
+    // Override for the length of food chains
+    pub food_chain: Option<u32>,
+    // Override for the percent chance of a wall breaker spawning when food is eaten
+    pub wall_breaker_chance: Option<u32>,
+    pub big_food_chance: Option<u32>,
+    // Overrides for the obstacle walls in a new level
+    pub obstacles: Option<usize>,
+    pub fill_pockets: Option<bool>,
+    pub new_layout_on_reset: Option<bool>,
+    // Overrides for the ghost walls in a new level
+    pub ghost_walls: Option<usize>,
+    pub ghost_wall_period: Option<u32>,
+    // Override for the length the snake stops growing at
+    pub max_length: Option<usize>,
+    // Override for whether the snake can bite off its own tail
+    pub tail_biting: Option<bool>,
+    // Overrides for the time attack rules
+    pub time_limit: Option<u32>,
+    pub overtime: Option<u32>,
+    pub overtime_condition: Option<OvertimeCondition>,
+    // Always spawn food as far away from the snake's head as possible
+    pub far_food: bool,
+    // Start a fresh game and don't save it on exit, regardless of the auto-save setting
+    pub no_save: bool,
+    // File to write a trace of every tick of the main game to
+    pub trace: Option<String>,
+    // Seconds between changes of the controls in the chaos mode, and the transforms of the
+    // controls it cycles through
+    pub chaos_interval: Option<u64>,
+    pub chaos_transforms: Option<Vec<ControlTransform>>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            boards: 1,
+            input_device: None,
+            list_controllers: false,
+            render_scale: 1.0,
+            debug: false,
+            start_mode: None,
+            food_decay: None,
+            food_value_floor: None,
+            food_protection_radius: None,
+            food_protection_ahead: None,
+            win_fill_percent: None,
+            ink_zones: None,
+            paint_goal: None,
+            food_chain: None,
+            wall_breaker_chance: None,
+            big_food_chance: None,
+            obstacles: None,
+            fill_pockets: None,
+            new_layout_on_reset: None,
+            ghost_walls: None,
+            ghost_wall_period: None,
+            max_length: None,
+            tail_biting: None,
+            time_limit: None,
+            overtime: None,
+            overtime_condition: None,
+            far_food: false,
+            no_save: false,
+            trace: None,
+            chaos_interval: None,
+            chaos_transforms: None,
+        }
+    }
+}
+
+impl Options {
+    // Whether the game is saved on exit and resumed on the next start
+    fn auto_save(&self, settings: &Settings) -> bool {
+        settings.auto_save && !self.no_save
+    }
+
+    // The given rules with any overrides from the options applied
+    fn rules(&self, rules: &Rules) -> Rules {
+        let mut rules = rules.clone();
+        if let Some(start_mode) = self.start_mode {
+            rules.start_mode = start_mode;
+        }
+        if let Some(food_decay) = self.food_decay {
+            rules.food_decay = food_decay;
+        }
+        if let Some(food_value_floor) = self.food_value_floor {
+            rules.food_value_floor = food_value_floor;
+        }
+        if let Some(radius) = self.food_protection_radius {
+            rules.food_protection_radius = radius;
+        }
+        if let Some(ahead) = self.food_protection_ahead {
+            rules.food_protection_ahead = ahead;
+        }
+        if let Some(percent) = self.win_fill_percent {
+            rules.win_fill_percent = percent;
+        }
+        if let Some(ink_zones) = self.ink_zones {
+            rules.ink_zones = ink_zones;
+        }
+        if let Some(paint_goal) = self.paint_goal {
+            rules.paint_goal = paint_goal;
+        }
+        if let Some(food_chain) = self.food_chain {
+            rules.food_chain = food_chain;
+        }
+        if let Some(chance) = self.wall_breaker_chance {
+            rules.wall_breaker_chance = chance;
+        }
+        if let Some(chance) = self.big_food_chance {
+            rules.big_food_chance = chance;
+        }
+        if let Some(obstacles) = self.obstacles {
+            rules.obstacles = obstacles;
+        }
+        if let Some(fill_pockets) = self.fill_pockets {
+            rules.fill_pockets = fill_pockets;
+        }
+        if let Some(new_layout_on_reset) = self.new_layout_on_reset {
+            rules.new_layout_on_reset = new_layout_on_reset;
+        }
+        if let Some(ghost_walls) = self.ghost_walls {
+            rules.ghost_walls = ghost_walls;
+        }
+        if let Some(period) = self.ghost_wall_period {
+            rules.ghost_wall_period = period;
+        }
+        if let Some(max_length) = self.max_length {
+            rules.max_length = max_length;
+        }
+        if let Some(tail_biting) = self.tail_biting {
+            rules.tail_biting = tail_biting;
+        }
+        if let Some(time_limit) = self.time_limit {
+            rules.time_limit = time_limit;
+        }
+        if let Some(overtime) = self.overtime {
+            rules.overtime = overtime;
+        }
+        if let Some(condition) = self.overtime_condition {
+            rules.overtime_condition = condition;
+        }
+        rules
+    }
+}
+
+pub fn init(options: Options) -> Result<Engine, String> {
+    if options.boards == 0 {
+        return Err("At least one board is required".to_string());
+    }
+    if !(options.render_scale > 0.0 && options.render_scale <= MAX_RENDER_SCALE) {
+        return Err(format!("The render scale must be above 0 and at most {}", MAX_RENDER_SCALE));
+    }
+    if options.win_fill_percent.is_some_and(|percent| percent == 0 || percent > 100) {
+        return Err("The win fill percentage must be between 1 and 100".to_string());
+    }
+    if options.paint_goal.is_some_and(|percent| percent == 0 || percent > 100) {
+        return Err("The paint goal must be between 1 and 100 percent".to_string());
+    }
+    if options.chaos_interval == Some(0) {
+        return Err("The interval between control changes must be at least a second".to_string());
+    }
+    let chaos = options.chaos_interval.map(|seconds| {
+        let mut chaos = ChaosControls::new(Duration::from_secs(seconds));
+        if let Some(ref transforms) = options.chaos_transforms {
+            chaos.transforms = transforms.clone();
+        }
+        chaos
+    });
+    let autopilot = options.boards > 1;
+    let mut settings: Settings = storage::load_or_default(&::APP_INFO, "settings");
+    if let Some(ref input_device) = options.input_device {
+        settings.input_device = input_device.clone();
+    }
+    let auto_save = options.auto_save(&settings);
+    let saved_game_state = if autopilot || !auto_save {
+        None
+    } else {
+        storage::load::<GameState>(&::APP_INFO, "game_state")
+    };
+    let (mut highscores, graveyard) = if autopilot {
+        (Highscores::default(), Graveyard::default())
+    } else {
+        (Highscores::load(&::APP_INFO), Graveyard::load(&::APP_INFO))
+    };
+    // Rules that shape the level only take effect in a saved game once it is reset
+    let (level_width, level_height) = DEFAULT_LEVEL_SIZE;
+    let mut game_state = match saved_game_state {
+        Some(game_state) => resume(game_state, &options, &mut highscores),
+        None => {
+            let mut game_state = GameState::with_rules(level_width,
+                                                       level_height,
+                                                       0,
+                                                       options.rules(&Rules::default()));
+            game_state.set_highscore(highscores.highscore_for(&game_state.level_id()));
+            game_state
+        }
+    };
+    let mut boards = (1..options.boards)
+        .map(|_| GameState::with_rules(level_width, level_height, 0, game_state.rules().clone()))
+        .collect::<Vec<_>>();
+    if options.far_food {
+        for game_state in ::std::iter::once(&mut game_state).chain(boards.iter_mut()) {
+            game_state.set_spawn_strategy(Box::new(FarthestFromHead));
+        }
+    }
+    let replay = Replay::new(&game_state);
+    let trace = match options.trace {
+        Some(ref path) => Some(Trace::create(path)?),
+        None => None,
+    };
     let tile_size = 8;
     let sdl = sdl2::init()?;
     let video = sdl.video()?;
     let event_pump = sdl.event_pump()?;
     let (level_width, level_height) = game_state.level_size();
-    let window = video.window("Snake",
-                level_width as u32 * tile_size,
-                level_height as u32 * tile_size)
+    let (columns, rows) = board_grid(options.boards);
+    let (logical_width, logical_height) = (columns as u32 * level_width as u32 * tile_size,
+                                           rows as u32 * level_height as u32 * tile_size);
+    let (window_width, window_height) =
+        scaled_size((logical_width, logical_height), options.render_scale);
+    let window = video.window("Snake", window_width, window_height)
         .build()
         .or_else(|e| Err(format!("{}", e)))?;
-    let renderer = window.renderer()
+    let mut renderer = window.renderer()
         .present_vsync()
         .build()
         .or_else(|e| Err(format!("{}", e)))?;
+    // Keep rendering at the unscaled size and let SDL scale it up to the window
+    renderer.set_logical_size(logical_width, logical_height)
+        .map_err(|e| format!("{}", e))?;
     let mouse = sdl.mouse();
+    let controller_subsystem = sdl.game_controller()?;
+    let controller = controller::open_chosen(&controller_subsystem, &settings.input_device)?;
+    if controller.is_none() && settings.input_device != InputDevice::Keyboard {
+        println!("No matching controller connected, using the keyboard");
+    }
 
     Ok(Engine {
            game_state: game_state,
+           settings: settings,
+           highscores: highscores,
+           graveyard: graveyard,
+           auto_save: auto_save,
            tile_size: tile_size,
            event_pump: event_pump,
            renderer: renderer,
            mouse: mouse,
+           controller_subsystem: controller_subsystem,
+           controller: controller,
+           boards: boards,
+           autopilot: autopilot,
+           turbo: false,
+           title: String::new(),
+           tick_rate: RateCounter::new(),
+           frame_rate: RateCounter::new(),
+           replay: replay,
+           replay_player: None,
+           debug: options.debug,
+           show_coordinates: false,
+           show_reachable: false,
+           show_bot_scores: false,
+           frame: 0,
+           shake_frames: 0,
+           shake_intensity: 0,
+           observers: Observers::default(),
+           trace: trace,
+           chaos: chaos,
+           game_start: None,
        })
 }
+
+// Continue a saved game with the rules from the given options
+fn resume(mut game_state: GameState, options: &Options, highscores: &mut Highscores) -> GameState {
+    game_state.fit_to_level();
+    // Saves from before the highscore was stored separately carry their own highscore, which
+    // belongs to the level they were saved on rather than the one the options may turn it into
+    let saved_level_id = game_state.level_id();
+    let highscore = ::std::cmp::max(highscores.highscore_for(&saved_level_id),
+                                    game_state.highscore());
+    highscores.set_highscore_for(&saved_level_id, highscore);
+    let rules = options.rules(game_state.rules());
+    game_state.set_rules(rules);
+    game_state.set_highscore(highscores.highscore_for(&game_state.level_id()));
+    game_state
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn turbo_speeds_up_ticks_while_the_current_direction_is_held() {
+        assert_eq!(tick_interval(true, Some(Direction::Right), Direction::Right, false),
+                   TURBO_TICK_INTERVAL);
+    }
+
+    #[test]
+    fn turbo_keeps_normal_speed_when_released_or_off() {
+        assert_eq!(tick_interval(true, None, Direction::Right, false), TICK_INTERVAL);
+        assert_eq!(tick_interval(false, Some(Direction::Right), Direction::Right, false),
+                   TICK_INTERVAL);
+    }
+
+    #[test]
+    fn turbo_keeps_normal_speed_for_turns() {
+        assert_eq!(tick_interval(true, Some(Direction::Up), Direction::Right, false),
+                   TICK_INTERVAL);
+        assert_eq!(tick_interval(true, Some(Direction::Right), Direction::Right, true),
+                   TICK_INTERVAL);
+    }
+
+    #[test]
+    fn turbo_follows_remapped_controls_in_chaos_mode() {
+        let chaos = ChaosControls {
+            interval: Duration::from_secs(10),
+            transforms: vec![ControlTransform::Reverse],
+        };
+        let held_dir = steer(Some(&chaos), Duration::from_secs(0), Direction::Left);
+        assert_eq!(tick_interval(true, Some(held_dir), Direction::Right, false),
+                   TURBO_TICK_INTERVAL);
+        let held_dir = steer(Some(&chaos), Duration::from_secs(0), Direction::Right);
+        assert_eq!(tick_interval(true, Some(held_dir), Direction::Right, false), TICK_INTERVAL);
+        assert_eq!(steer(None, Duration::from_secs(0), Direction::Right), Direction::Right);
+    }
+
+    #[test]
+    fn resumed_games_keep_their_highscore_with_the_level_they_were_saved_on() {
+        let mut saved = GameState::default();
+        saved.set_highscore(40);
+        let mut highscores = Highscores::default();
+        let options = Options { obstacles: Some(20), ..Options::default() };
+        let resumed = resume(saved.clone(), &options, &mut highscores);
+        assert_ne!(resumed.level_id(), "default");
+        assert_eq!(resumed.highscore(), 0);
+        assert_eq!(highscores.highscore_for("default"), 40);
+        assert_eq!(highscores.highscore_for(&resumed.level_id()), 0);
+
+        let resumed = resume(saved, &Options::default(), &mut highscores);
+        assert_eq!(resumed.highscore(), 40);
+    }
+
+    #[test]
+    fn no_save_flag_turns_off_auto_save() {
+        let options = Options { no_save: true, ..Options::default() };
+        assert!(!options.auto_save(&Settings::default()));
+        assert!(Options::default().auto_save(&Settings::default()));
+    }
+
+    // Names of the files saved in a fresh directory with the given options
+    fn saved_files(name: &str, options: &Options, autopilot: bool) -> Vec<String> {
+        let dir = ::std::env::temp_dir()
+            .join(format!("snake-engine-{}-{}", ::std::process::id(), name));
+        let _ = ::std::fs::remove_dir_all(&dir);
+        ::std::fs::create_dir_all(&dir).unwrap();
+        let settings = Settings::default();
+        save_in(&dir,
+                &settings,
+                &Highscores::default(),
+                &Graveyard::default(),
+                &GameState::default(),
+                autopilot,
+                options.auto_save(&settings))
+            .unwrap();
+        let mut files = ::std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        files.sort();
+        ::std::fs::remove_dir_all(&dir).unwrap();
+        files
+    }
+
+    #[test]
+    fn no_save_flag_writes_no_game_state() {
+        let options = Options { no_save: true, ..Options::default() };
+        assert_eq!(saved_files("no-save", &options, false),
+                   ["graveyard.prefs.json", "highscores.prefs.json", "settings.prefs.json"]);
+        assert_eq!(saved_files("auto-save", &Options::default(), false),
+                   ["game_state.prefs.json",
+                    "graveyard.prefs.json",
+                    "highscores.prefs.json",
+                    "settings.prefs.json"]);
+        assert_eq!(saved_files("autopilot", &Options::default(), true),
+                   ["settings.prefs.json"]);
+    }
+
+    #[test]
+    fn auto_save_setting_can_turn_off_saving_without_the_flag() {
+        let settings = Settings { auto_save: false, ..Settings::default() };
+        assert!(!Options::default().auto_save(&settings));
+    }
+
+    #[test]
+    fn window_size_follows_the_render_scale() {
+        assert_eq!(scaled_size((640, 480), 1.0), (640, 480));
+        assert_eq!(scaled_size((640, 480), 2.0), (1280, 960));
+        assert_eq!(scaled_size((640, 480), 0.5), (320, 240));
+        assert_eq!(scaled_size((640, 480), 1.3), (832, 624));
+    }
+
+    #[test]
+    fn shake_starts_at_full_intensity_and_decays_to_nothing() {
+        assert_eq!(shake_amplitude(SHAKE_FRAMES, DEATH_SHAKE), DEATH_SHAKE);
+        let amplitudes = (0..SHAKE_FRAMES + 1)
+            .rev()
+            .map(|frames_left| shake_amplitude(frames_left, DEATH_SHAKE))
+            .collect::<Vec<_>>();
+        assert!(amplitudes.windows(2).all(|pair| pair[0] >= pair[1]));
+        assert_eq!(shake_amplitude(1, DEATH_SHAKE), 1);
+        assert_eq!(shake_amplitude(0, DEATH_SHAKE), 0);
+    }
+
+    #[test]
+    fn each_kind_of_tile_has_its_own_pattern() {
+        assert_eq!(tile_pattern(Tile::Wall), Pattern::Cross);
+        assert_eq!(tile_pattern(Tile::Food), Pattern::Dot);
+        assert_eq!(tile_pattern(Tile::BigFood { top: true, left: false }), Pattern::Dot);
+        assert_eq!(tile_pattern(Tile::ChainFood(2)), Pattern::Dot);
+        assert_eq!(tile_pattern(Tile::WallBreaker), Pattern::Bar);
+        assert_eq!(tile_pattern(Tile::Floor), Pattern::None);
+        assert_eq!(tile_pattern(Tile::Snake(None, Some(Direction::Right))), Pattern::None);
+    }
+
+    #[test]
+    fn patterns_stay_inside_their_tile() {
+        for &pattern in &[Pattern::None, Pattern::Cross, Pattern::Dot, Pattern::Bar] {
+            for rect in pattern_rects((2, 3), pattern, 10) {
+                assert!(rect.left() >= 30 && rect.right() <= 40, "{:?}", rect);
+                assert!(rect.top() >= 20 && rect.bottom() <= 30, "{:?}", rect);
+            }
+        }
+    }
+
+    #[test]
+    fn edge_glow_stays_between_dark_and_bright() {
+        for frame in 0..EDGE_GLOW_PERIOD * 2 {
+            let intensity = edge_glow_intensity(frame);
+            assert!((0.0..=1.0).contains(&intensity), "frame {}: {}", frame, intensity);
+        }
+        assert!(edge_glow_intensity(0) < 1e-6);
+        assert!(edge_glow_intensity(EDGE_GLOW_PERIOD / 2) > 1.0 - 1e-6);
+    }
+
+    #[test]
+    fn edge_glow_repeats_every_period() {
+        for frame in 0..EDGE_GLOW_PERIOD {
+            assert_eq!(edge_glow_intensity(frame), edge_glow_intensity(frame + EDGE_GLOW_PERIOD));
+        }
+    }
+}
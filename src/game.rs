@@ -1,12 +1,19 @@
+use std::collections::HashMap;
 use ndarray;
 use rand;
-use rand::Rng;
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use highscores::DEFAULT_LEVEL_ID;
+use spawn::{BoxedSpawnStrategy, SpawnStrategy, UniformRandom};
 
 type TileArray = ndarray::Array2<Tile>;
+type ZoneArray = ndarray::Array2<Zone>;
+type GhostWallArray = ndarray::Array2<bool>;
+type PaintArray = ndarray::Array2<bool>;
 // Index into a TileArray; arrays are indexed in (row (y), column (x)) order
-type TileIndex = (usize, usize);
+pub type TileIndex = (usize, usize);
 
-#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Direction {
     Up,
     Down,
@@ -14,8 +21,13 @@ pub enum Direction {
     Right,
 }
 
+pub const DIRECTIONS: [Direction; 4] = [Direction::Up,
+                                        Direction::Down,
+                                        Direction::Left,
+                                        Direction::Right];
+
 impl Direction {
-    fn reverse(&self) -> Direction {
+    pub fn reverse(&self) -> Direction {
         match *self {
             Direction::Up => Direction::Down,
             Direction::Down => Direction::Up,
@@ -30,33 +42,340 @@ pub enum Tile {
     Floor,
     Wall,
     Food,
+    // One quarter of a 2x2 block of food that is eaten one tile at a time, marking whether the
+    // tile is in the top row and left column of its block
+    BigFood { top: bool, left: bool },
+    // Food that is part of a chain in food chain mode, with its place in the order it has to be
+    // eaten in, counting from 1
+    ChainFood(u32),
+    // Power-up letting the snake break through one wall
+    WallBreaker,
     // Snake contains the optional directions towards the previous and next snake segments.  The
     // tail has only a next direction (so a Snake(None, Some(_))) while the head has only a
     // previous direction (so a Snake(Some(_), None)); all other segments have both defined.
     Snake(Option<Direction>, Option<Direction>),
 }
 
-#[derive(Serialize, Deserialize)]
+pub const DEFAULT_LEVEL_SIZE: (usize, usize) = (40, 30);
+// Smallest level the starting snake fits into without touching the walls
+const MIN_LEVEL_SIZE: (usize, usize) = (7, 5);
+
+// Length of the snake at the start of a game
+const START_LENGTH: usize = 3;
+
+// Number of tiles ahead of the snake's starting position that are kept clear of obstacles
+const START_ZONE_LENGTH: usize = 5;
+
+// Ink zones are floor tiles awarding a bonus the first time the snake's head passes over them
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum Zone {
+    Plain,
+    Uninked,
+    Inked,
+}
+
+// What happens when the snake moves off an edge of the level that isn't walled off
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum EdgeBehavior {
+    // Wrap around to the opposite edge immediately
+    Wrap,
+    // Stop at the edge for one tick as a warning, then wrap if the snake keeps heading across it
+    WarnThenWrap,
+}
+
+// When the snake starts moving in a new game
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum StartMode {
+    // Straight away, in the direction it starts out facing
+    Immediate,
+    // Only once the player first picks a direction
+    WaitForInput,
+}
+
+// When a time attack game gets overtime instead of ending as time runs out
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum OvertimeCondition {
+    // The snake is at most this many segments short of the length needed to win
+    NearWin { segments: usize },
+    // The snake ate food within this many ticks
+    RecentlyAte { ticks: u32 },
+}
+
+// Configurable game rules; these survive a reset
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Rules {
+    pub start_mode: StartMode,
+    pub edge_behavior: EdgeBehavior,
+    // Score for eating food as soon as it spawns
+    pub food_value: u32,
+    // Score lost for every tick the food sits uneaten; 0 disables decay
+    pub food_decay: u32,
+    // Decay never lowers the value of food below this
+    pub food_value_floor: u32,
+    // Food never spawns within this distance of the snake's head, or this many tiles ahead of it
+    pub food_protection_radius: usize,
+    pub food_protection_ahead: usize,
+    // Percentage of the level's non-wall tiles the snake has to fill to win
+    pub win_fill_percent: u32,
+    // Number of ink zones placed in a new level, and the bonus for inking each of them; inking
+    // all zones wins the game
+    pub ink_zones: usize,
+    pub ink_zone_bonus: u32,
+    // Percentage of the level's non-wall tiles the snake's head has to paint by visiting them to
+    // win; 0 disables painting
+    pub paint_goal: u32,
+    // Number of food in a chain, which has to be eaten in order and is replaced by a new chain
+    // once eaten; eating food out of order ends the game.  0 spawns food one at a time instead.
+    pub food_chain: u32,
+    // Percent chance of a wall breaker power-up spawning whenever food is eaten
+    pub wall_breaker_chance: u32,
+    // Percent chance of big food spawning whenever food is eaten, and the score for each bite
+    pub big_food_chance: u32,
+    pub big_food_bite_value: u32,
+    // Number of obstacle walls placed inside a new level, and whether to wall up single tiles
+    // the obstacles enclose
+    pub obstacles: usize,
+    pub fill_pockets: bool,
+    // Whether resetting generates a new level layout rather than restarting the current one
+    pub new_layout_on_reset: bool,
+    // Number of ghost walls placed in a new level, which are solid and phased out in turns for
+    // this many ticks each
+    pub ghost_walls: usize,
+    pub ghost_wall_period: u32,
+    // Whether steering the head into the tail bites it off, shrinking the snake at the cost of
+    // some score, instead of just following the tail
+    pub tail_biting: bool,
+    pub tail_bite_penalty: u32,
+    // Length beyond which the snake doesn't grow any further; 0 lets it grow without limit
+    pub max_length: usize,
+    // Number of ticks a time attack game lasts; 0 disables the time limit
+    pub time_limit: u32,
+    // Extra ticks granted once when time runs out while the overtime condition holds; 0
+    // disables overtime
+    pub overtime: u32,
+    pub overtime_condition: OvertimeCondition,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            start_mode: StartMode::Immediate,
+            edge_behavior: EdgeBehavior::Wrap,
+            food_value: 10,
+            food_decay: 0,
+            food_value_floor: 0,
+            food_protection_radius: 0,
+            food_protection_ahead: 0,
+            win_fill_percent: 100,
+            ink_zones: 0,
+            ink_zone_bonus: 5,
+            paint_goal: 0,
+            food_chain: 0,
+            wall_breaker_chance: 0,
+            big_food_chance: 0,
+            big_food_bite_value: 5,
+            obstacles: 0,
+            fill_pockets: true,
+            new_layout_on_reset: false,
+            ghost_walls: 0,
+            ghost_wall_period: 20,
+            tail_biting: false,
+            tail_bite_penalty: 5,
+            max_length: 0,
+            time_limit: 0,
+            overtime: 0,
+            overtime_condition: OvertimeCondition::NearWin { segments: 3 },
+        }
+    }
+}
+
+// Things that happened during a game update
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    AteFood { index: TileIndex, score: u32 },
+    SpawnedFood { index: TileIndex },
+    Died { index: TileIndex },
+    InkedZone { index: TileIndex, score: u32 },
+    Overtime { ticks: u32 },
+    TimeUp,
+    GotWallBreaker { index: TileIndex },
+    BitTail { index: TileIndex },
+    BrokeWall { index: TileIndex },
+    Won,
+}
+
+type Observer = Box<dyn FnMut(&GameEvent)>;
+
+// Callbacks notified of game events, as an alternative to inspecting the events returned by
+// GameState::update.  Observers are called after the update that produced the events has
+// finished, once per event in the order the events happened, and in the order the observers
+// were registered.  They only get to see the event itself: the game state is still borrowed by
+// whoever drives the game, so any state an observer needs to keep has to be shared with it, for
+// example through an Rc<Cell<_>>.  Food spawned when a level is set up is reported along with
+// the events of the first update on it.
+#[derive(Default)]
+pub struct Observers {
+    observers: Vec<Observer>,
+}
+
+impl Observers {
+    // Register a callback for all events
+    pub fn on_event<F: FnMut(&GameEvent) + 'static>(&mut self, observer: F) {
+        self.observers.push(Box::new(observer));
+    }
+
+    pub fn on_eat<F: FnMut(&GameEvent) + 'static>(&mut self, mut observer: F) {
+        self.on_event(move |event| {
+            if let GameEvent::AteFood { .. } = *event {
+                observer(event);
+            }
+        });
+    }
+
+    pub fn on_death<F: FnMut(&GameEvent) + 'static>(&mut self, mut observer: F) {
+        self.on_event(move |event| {
+            if let GameEvent::Died { .. } = *event {
+                observer(event);
+            }
+        });
+    }
+
+    pub fn on_spawn<F: FnMut(&GameEvent) + 'static>(&mut self, mut observer: F) {
+        self.on_event(move |event| {
+            if let GameEvent::SpawnedFood { .. } = *event {
+                observer(event);
+            }
+        });
+    }
+
+    pub fn notify(&mut self, events: &[GameEvent]) {
+        for event in events {
+            for observer in &mut self.observers {
+                observer(event);
+            }
+        }
+    }
+}
+
+// The part of the snake occupying a tile, as returned by GameState::snake_part_at
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SnakePart {
+    Head,
+    Body,
+    Tail,
+}
+
+// Every game gets its own random number generator, so that a copy of a game state replays
+// identically given the same inputs
+fn new_rng() -> XorShiftRng {
+    rand::thread_rng().gen()
+}
+
+fn default_spawn_strategy() -> BoxedSpawnStrategy {
+    Box::new(UniformRandom::new())
+}
+
+// Stand-in for a game's spawn strategy while the strategy itself is busy looking at the game
+struct Picking;
+
+impl SpawnStrategy for Picking {
+    fn pick(&mut self, _state: &GameState) -> Option<TileIndex> {
+        None
+    }
+
+    fn box_clone(&self) -> BoxedSpawnStrategy {
+        Box::new(Picking)
+    }
+}
+
+// Fields missing from saves made by older versions take their values from a default game, so
+// a loaded game should be passed through fit_to_level before it is played
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
 pub struct GameState {
     level_width: usize,
     level_height: usize,
     tiles: TileArray,
+    zones: ZoneArray,
+    // Floor tiles that turn into walls whenever ghost walls are solid
+    ghost_walls: GhostWallArray,
+    // Tiles the snake's head has visited, which are painted whether or not painting is enabled
+    painted: PaintArray,
     snake_head_idx: TileIndex,
     snake_tail_idx: TileIndex,
     snake_dir: Direction,
     snake_alive: bool,
+    // Whether the snake has started moving
+    started: bool,
     score: u32,
     highscore: u32,
+    rules: Rules,
+    // Edge (identified by the direction crossing it) the snake has been warned about
+    pending_edge: Option<Direction>,
+    // Number of ticks the snake has moved, and the tick on which the current food spawned
+    tick: u32,
+    food_spawn_tick: u32,
+    // Tick on which the snake last ate, and how many times it ate so far
+    last_eat_tick: Option<u32>,
+    food_eaten: u32,
+    // Place in the order of the chain food that has to be eaten next in food chain mode, and
+    // how many foods the current chain has, which is fewer than the rules ask for if not all of
+    // them fit on the level
+    expected_food: u32,
+    chain_length: u32,
+    // Whether time ran out in time attack, and whether overtime was granted before that
+    timed_out: bool,
+    overtime_granted: bool,
+    // Number of tiles that aren't walls, counted whenever the level layout changes
+    free_tile_count: usize,
+    won: bool,
+    // Whether the level is surrounded by walls
+    walls: bool,
+    // Number of walls the snake can still break through
+    wall_break_charges: u32,
+    #[serde(skip_serializing, skip_deserializing, default = "new_rng")]
+    rng: XorShiftRng,
+    // Seed the random parts of the level layout (obstacles and ink zones) are generated from
+    level_seed: u32,
+    // Strategies can't be saved, so a loaded game always goes back to the default one
+    #[serde(skip_serializing, skip_deserializing, default = "default_spawn_strategy")]
+    spawn_strategy: BoxedSpawnStrategy,
+    // Events that happened while the level was set up, such as the first food spawning, which
+    // are returned along with those of the next update
+    #[serde(skip_serializing, skip_deserializing)]
+    pending_events: Vec<GameEvent>,
 }
 
 impl GameState {
     pub fn new(level_width: usize, level_height: usize, highscore: u32) -> Self {
-        let mut tiles = ndarray::Array::from_elem((level_height, level_width), Tile::Floor);
+        Self::with_rules(level_width, level_height, highscore, Rules::default())
+    }
+
+    pub fn with_rules(level_width: usize,
+                      level_height: usize,
+                      highscore: u32,
+                      rules: Rules)
+                      -> Self {
+        let level_seed = rand::thread_rng().gen();
+        Self::generate(level_width,
+                       level_height,
+                       highscore,
+                       rules,
+                       true,
+                       level_seed,
+                       default_spawn_strategy())
+    }
 
-        // Place snake
-        tiles[(3, 3)] = Tile::Snake(None, Some(Direction::Right));
-        tiles[(3, 4)] = Tile::Snake(Some(Direction::Left), Some(Direction::Right));
-        tiles[(3, 5)] = Tile::Snake(Some(Direction::Left), None);
+    fn generate(level_width: usize,
+                level_height: usize,
+                highscore: u32,
+                rules: Rules,
+                walls: bool,
+                level_seed: u32,
+                spawn_strategy: BoxedSpawnStrategy)
+                -> Self {
+        let tiles = ndarray::Array::from_elem((level_height, level_width), Tile::Floor);
         let snake_head_idx = (3, 5);
         let snake_tail_idx = (3, 3);
         let snake_dir = Direction::Right;
@@ -67,44 +386,333 @@ impl GameState {
             level_width: level_width,
             level_height: level_height,
             tiles: tiles,
+            zones: ndarray::Array::from_elem((level_height, level_width), Zone::Plain),
+            ghost_walls: ndarray::Array::from_elem((level_height, level_width), false),
+            painted: ndarray::Array::from_elem((level_height, level_width), false),
             snake_head_idx: snake_head_idx,
             snake_tail_idx: snake_tail_idx,
             snake_dir: snake_dir,
             snake_alive: snake_alive,
+            started: rules.start_mode == StartMode::Immediate,
             score: score,
             highscore: highscore,
+            rules: rules,
+            pending_edge: None,
+            tick: 0,
+            food_spawn_tick: 0,
+            last_eat_tick: None,
+            food_eaten: 0,
+            expected_food: 1,
+            chain_length: 0,
+            timed_out: false,
+            overtime_granted: false,
+            free_tile_count: 0,
+            won: false,
+            walls: false,
+            wall_break_charges: 0,
+            rng: new_rng(),
+            level_seed: level_seed,
+            spawn_strategy: spawn_strategy,
+            pending_events: Vec::new(),
+        };
+        // The layout gets its own generator, so that the same seed always gives the same level
+        let mut level_rng = XorShiftRng::from_seed([level_seed, 0x9e37_79b9, 0x7f4a_7c15, 1]);
+        if walls {
+            game_state.toggle_walls();
+        }
+        // Place snake, moving it elsewhere if the usual start would trap it
+        let (tail, dir) = if game_state.is_safe_spawn(snake_tail_idx, snake_dir, START_LENGTH) {
+            (snake_tail_idx, snake_dir)
+        } else {
+            game_state.find_safe_spawn(START_LENGTH).unwrap_or((snake_tail_idx, snake_dir))
         };
-        game_state.toggle_walls();
-        game_state.spawn_food();
+        game_state.place_snake(tail, dir, START_LENGTH);
+        let head = game_state.snake_head_idx;
+        game_state.painted[head] = true;
+        game_state.place_obstacles(&mut level_rng);
+        game_state.place_ink_zones(&mut level_rng);
+        game_state.place_ghost_walls(&mut level_rng);
+        let spawned = if game_state.rules.food_chain > 0 {
+            game_state.spawn_food_chain()
+        } else {
+            game_state.spawn_food().into_iter().collect()
+        };
+        game_state.pending_events =
+            spawned.into_iter().map(|index| GameEvent::SpawnedFood { index: index }).collect();
 
         game_state
     }
 
-    fn swap_tile(&mut self, i: TileIndex, tile1: Tile, tile2: Tile) {
-        let tile = self.tiles[i];
-        if tile == tile1 {
-            self.tiles[i] = tile2;
-        } else if tile == tile2 {
-            self.tiles[i] = tile1;
+    // Whether a snake of the given length can start out with its tail on the given tile, heading
+    // in the given direction: the snake's tiles and the start zone ahead of it must all be floor,
+    // without wrapping around the level edges
+    fn is_safe_spawn(&self, tail: TileIndex, dir: Direction, length: usize) -> bool {
+        let mut index = tail;
+        for i in 0..length + START_ZONE_LENGTH {
+            if self.tiles[index] != Tile::Floor {
+                return false;
+            }
+            if i + 1 < length + START_ZONE_LENGTH {
+                if self.crosses_edge(index, dir) {
+                    return false;
+                }
+                index = self.add_dir_to_index(index, dir);
+            }
+        }
+        true
+    }
+
+    // Find somewhere a snake of the given length can safely start out, returning the tile for its
+    // tail and the direction it heads in.  Horizontal runs of floor are preferred over vertical
+    // ones, and the first run found in row-major order is used.
+    pub fn find_safe_spawn(&self, length: usize) -> Option<(TileIndex, Direction)> {
+        [Direction::Right, Direction::Down]
+            .iter()
+            .flat_map(|&dir| self.tiles.indexed_iter().map(move |(index, _)| (index, dir)))
+            .find(|&(index, dir)| self.is_safe_spawn(index, dir, length))
+    }
+
+    // Place a snake of the given length on the level, from its tail heading in the given
+    // direction
+    fn place_snake(&mut self, tail: TileIndex, dir: Direction, length: usize) {
+        let mut index = tail;
+        for i in 0..length {
+            let prev = if i == 0 { None } else { Some(dir.reverse()) };
+            let next = if i + 1 == length { None } else { Some(dir) };
+            self.tiles[index] = Tile::Snake(prev, next);
+            if next.is_some() {
+                index = self.add_dir_to_index(index, dir);
+            }
+        }
+        self.snake_tail_idx = tail;
+        self.snake_head_idx = index;
+        self.snake_dir = dir;
+    }
+
+    fn floor_tiles(&self) -> Vec<TileIndex> {
+        self.tiles
+            .indexed_iter()
+            .filter(|&(_, &tile)| tile == Tile::Floor)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    // Whether a tile is part of the snake's starting position or the path right ahead of it,
+    // which obstacles must keep clear so the snake isn't trapped from the start
+    fn is_start_zone(&self, index: TileIndex) -> bool {
+        if let Tile::Snake(..) = self.tiles[index] {
+            return true;
+        }
+        let mut ahead = self.snake_head_idx;
+        for _ in 0..START_ZONE_LENGTH {
+            ahead = self.add_dir_to_index(ahead, self.snake_dir);
+            if ahead == index {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn place_obstacles(&mut self, rng: &mut XorShiftRng) {
+        let (w, h) = (self.level_width, self.level_height);
+        let mut free: Vec<TileIndex> = self.floor_tiles()
+            .into_iter()
+            .filter(|&(y, x)| y > 0 && y < h - 1 && x > 0 && x < w - 1)
+            .filter(|&index| !self.is_start_zone(index))
+            .collect();
+        rng.shuffle(&mut free);
+        for &index in free.iter().take(self.rules.obstacles) {
+            self.tiles[index] = Tile::Wall;
+        }
+        if self.rules.fill_pockets {
+            self.fill_pockets();
+        }
+        self.count_free_tiles();
+    }
+
+    // Turn floor tiles that are walled in on all sides into walls, so food can't spawn in them
+    fn fill_pockets(&mut self) {
+        for index in self.floor_tiles() {
+            if DIRECTIONS.iter()
+                .all(|&dir| self.tiles[self.add_dir_to_index(index, dir)] == Tile::Wall) {
+                self.tiles[index] = Tile::Wall;
+            }
+        }
+    }
+
+    fn place_ink_zones(&mut self, rng: &mut XorShiftRng) {
+        let mut free = self.floor_tiles();
+        rng.shuffle(&mut free);
+        for &index in free.iter().take(self.rules.ink_zones) {
+            self.zones[index] = Zone::Uninked;
+        }
+    }
+
+    fn place_ghost_walls(&mut self, rng: &mut XorShiftRng) {
+        let (w, h) = (self.level_width, self.level_height);
+        let mut free: Vec<TileIndex> = self.floor_tiles()
+            .into_iter()
+            .filter(|&(y, x)| y > 0 && y < h - 1 && x > 0 && x < w - 1)
+            .filter(|&index| !self.is_start_zone(index) && self.zones[index] == Zone::Plain)
+            .collect();
+        rng.shuffle(&mut free);
+        for &index in free.iter().take(self.rules.ghost_walls) {
+            self.ghost_walls[index] = true;
+        }
+    }
+
+    pub fn ghost_walls(&self) -> &GhostWallArray {
+        &self.ghost_walls
+    }
+
+    // Whether ghost walls are solid on the given tick; they start out solid, and stay solid for
+    // good with a period of 0
+    fn ghost_walls_solid_on(&self, tick: u32) -> bool {
+        let period = self.rules.ghost_wall_period;
+        period == 0 || (tick / period).is_multiple_of(2)
+    }
+
+    // Whether ghost walls are solid for the snake's next move
+    pub fn ghost_walls_solid(&self) -> bool {
+        self.ghost_walls_solid_on(self.tick + 1)
+    }
+
+    // Number of moves left until ghost walls next change between solid and phased out, or None
+    // if they never do
+    pub fn ticks_until_ghost_phase_change(&self) -> Option<u32> {
+        let period = self.rules.ghost_wall_period;
+        if period == 0 {
+            return None;
+        }
+        Some(period - (self.tick + 1) % period)
+    }
+
+    fn is_solid_ghost_wall(&self, index: TileIndex, tick: u32) -> bool {
+        self.ghost_walls[index] && self.ghost_walls_solid_on(tick)
+    }
+
+    fn replace_tile(&mut self, i: TileIndex, from: Tile, to: Tile) {
+        if self.tiles[i] == from {
+            self.tiles[i] = to;
         }
     }
 
     pub fn toggle_walls(&mut self) {
-        // Build level wall
+        // Build or tear down level wall; rebuilding it also closes any holes broken into it
+        self.walls = !self.walls;
+        let (from, to) = if self.walls {
+            (Tile::Floor, Tile::Wall)
+        } else {
+            (Tile::Wall, Tile::Floor)
+        };
         let (w, h) = (self.level_width, self.level_height);
         for x in 0..w {
-            self.swap_tile((0, x), Tile::Floor, Tile::Wall);
-            self.swap_tile((h - 1, x), Tile::Floor, Tile::Wall);
+            self.replace_tile((0, x), from, to);
+            self.replace_tile((h - 1, x), from, to);
         }
         for y in 1..h - 1 {
-            self.swap_tile((y, 0), Tile::Floor, Tile::Wall);
-            self.swap_tile((y, w - 1), Tile::Floor, Tile::Wall);
+            self.replace_tile((y, 0), from, to);
+            self.replace_tile((y, w - 1), from, to);
         }
+        self.count_free_tiles();
+    }
+
+    pub fn walls(&self) -> bool {
+        self.walls
+    }
+
+    pub fn wall_break_charges(&self) -> u32 {
+        self.wall_break_charges
+    }
+
+    fn count_free_tiles(&mut self) {
+        self.free_tile_count = self.tiles.iter().filter(|&&tile| tile != Tile::Wall).count();
+    }
+
+    // Make a loaded game consistent with its level, whose layout is taken from the saved tiles;
+    // layers a save didn't have are cleared to the size of the level
+    pub fn fit_to_level(&mut self) {
+        let (level_height, level_width) = self.tiles.dim();
+        self.level_width = level_width;
+        self.level_height = level_height;
+        if self.zones.dim() != self.tiles.dim() {
+            self.zones = ndarray::Array::from_elem(self.tiles.dim(), Zone::Plain);
+        }
+        if self.ghost_walls.dim() != self.tiles.dim() {
+            self.ghost_walls = ndarray::Array::from_elem(self.tiles.dim(), false);
+        }
+        if self.painted.dim() != self.tiles.dim() {
+            self.painted = ndarray::Array::from_elem(self.tiles.dim(), false);
+        }
+        // Obstacles are never placed on the border, so any wall there is part of the level wall
+        let (h, w) = (level_height, level_width);
+        self.walls = (0..w).any(|x| self.tiles[(0, x)] == Tile::Wall ||
+                                    self.tiles[(h - 1, x)] == Tile::Wall) ||
+                     (0..h).any(|y| self.tiles[(y, 0)] == Tile::Wall ||
+                                    self.tiles[(y, w - 1)] == Tile::Wall);
+        self.count_free_tiles();
     }
 
 
     pub fn reset(&mut self) {
-        *self = GameState::new(self.level_width, self.level_height, self.highscore);
+        let level_seed = if self.rules.new_layout_on_reset {
+            rand::thread_rng().gen()
+        } else {
+            self.level_seed
+        };
+        *self = GameState::generate(self.level_width,
+                                    self.level_height,
+                                    self.highscore,
+                                    self.rules.clone(),
+                                    self.walls,
+                                    level_seed,
+                                    self.spawn_strategy.clone());
+    }
+
+    // Replace the game with a new one built from the given configuration, which may differ from
+    // the current one in anything from the level size to the seed.  Unlike a reset, the score
+    // carries over into the new game, as does the highscore and the spawn strategy.
+    pub fn rebuild(&mut self, builder: GameStateBuilder) -> Result<(), String> {
+        let mut game_state = builder.highscore(self.highscore).build()?;
+        game_state.score = self.score;
+        game_state.spawn_strategy = self.spawn_strategy.clone();
+        *self = game_state;
+        Ok(())
+    }
+
+    pub fn level_seed(&self) -> u32 {
+        self.level_seed
+    }
+
+    // Identifier of the level being played, for keeping highscores per level.  Generated layouts
+    // are told apart by their seed, except when every reset generates a new one anyway.  Paint
+    // and food chain modes score differently enough to count as levels of their own.
+    pub fn level_id(&self) -> String {
+        let generated = self.rules.obstacles > 0 || self.rules.ink_zones > 0 ||
+                        self.rules.ghost_walls > 0;
+        let mut level_id = if !generated {
+            DEFAULT_LEVEL_ID.to_string()
+        } else if self.rules.new_layout_on_reset {
+            "random".to_string()
+        } else {
+            format!("seed {}", self.level_seed)
+        };
+        if self.rules.paint_goal > 0 {
+            level_id.push_str(&format!(", paint {}%", self.rules.paint_goal));
+        }
+        if self.rules.food_chain > 0 {
+            level_id.push_str(&format!(", chain {}", self.rules.food_chain));
+        }
+        level_id
+    }
+
+    pub fn rules(&self) -> &Rules {
+        &self.rules
+    }
+
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        self.pending_edge = None;
     }
 
     pub fn level_size(&self) -> (usize, usize) {
@@ -115,23 +723,291 @@ impl GameState {
         self.snake_alive
     }
 
-    fn spawn_food(&mut self) {
-        let mut rng = rand::thread_rng();
-        let mut index;
-        // FIXME This will hang if the snake fills the entire playing field
-        loop {
-            index = (rng.gen_range(0, self.level_height), rng.gen_range(0, self.level_width));
-            if self.tiles[index] == Tile::Floor {
-                break;
-            };
+    pub fn snake_dir(&self) -> Direction {
+        self.snake_dir
+    }
+
+    pub fn snake_head(&self) -> TileIndex {
+        self.snake_head_idx
+    }
+
+    // Number of ticks the snake has moved so far
+    pub fn tick(&self) -> u32 {
+        self.tick
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn highscore(&self) -> u32 {
+        self.highscore
+    }
+
+    pub fn set_highscore(&mut self, highscore: u32) {
+        self.highscore = highscore;
+    }
+
+    // Indices of all snake segments, ordered from tail to head
+    pub fn snake_segments(&self) -> Vec<TileIndex> {
+        let mut index = self.snake_tail_idx;
+        let mut segments = vec![index];
+        while let Tile::Snake(_, Some(next)) = self.tiles[index] {
+            index = self.add_dir_to_index(index, next);
+            segments.push(index);
+        }
+        segments
+    }
+
+    // Smallest and largest (row, column) occupied by the snake.  The box is computed in raw index
+    // space, so a snake straddling a wrapped edge spans the whole width or height between its
+    // two halves rather than just the tiles it covers.
+    pub fn snake_bounds(&self) -> (TileIndex, TileIndex) {
+        let mut segments = self.snake_segments().into_iter();
+        let first = segments.next().unwrap_or(self.snake_head_idx);
+        segments.fold((first, first), |((min_y, min_x), (max_y, max_x)), (y, x)| {
+            ((min_y.min(y), min_x.min(x)), (max_y.max(y), max_x.max(x)))
+        })
+    }
+
+    pub fn snake_length(&self) -> usize {
+        self.snake_segments().len()
+    }
+
+    pub fn painted(&self) -> &PaintArray {
+        &self.painted
+    }
+
+    // Number of painted tiles that aren't walls, which walls rebuilt over painted tiles don't
+    // count towards
+    fn painted_tile_count(&self) -> usize {
+        self.painted
+            .iter()
+            .zip(self.tiles.iter())
+            .filter(|&(&painted, &tile)| painted && tile != Tile::Wall)
+            .count()
+    }
+
+    // Percentage of the level's non-wall tiles the snake has painted, rounded down
+    pub fn coverage_percent(&self) -> u32 {
+        (self.painted_tile_count() * 100).checked_div(self.free_tile_count).unwrap_or(0) as u32
+    }
+
+    // Whether the snake has painted enough of the level to win
+    fn painted_enough(&self) -> bool {
+        self.rules.paint_goal > 0 &&
+        self.painted_tile_count() * 100 >= self.free_tile_count * self.rules.paint_goal as usize
+    }
+
+    // Length the snake has to reach to win
+    pub fn win_length(&self) -> usize {
+        (self.free_tile_count * self.rules.win_fill_percent as usize).div_ceil(100)
+    }
+
+    // Fraction of the length needed to win the snake has reached so far, from 0 to 1
+    pub fn win_progress(&self) -> f32 {
+        let win_length = self.win_length();
+        if win_length == 0 {
+            return 1.0;
+        }
+        (self.snake_length() as f32 / win_length as f32).min(1.0)
+    }
+
+    pub fn won(&self) -> bool {
+        self.won
+    }
+
+    // Whether the snake has been got going, as opposed to still waiting for its first input
+    pub fn started(&self) -> bool {
+        self.started
+    }
+
+    // Whether the snake moved on the last tick and keeps moving, as opposed to waiting to start,
+    // being held at an edge or having stopped for good
+    pub fn snake_moving(&self) -> bool {
+        self.started && self.pending_edge.is_none() && !self.game_over()
+    }
+
+    // Number of times the snake ate, counting every bite of big food
+    pub fn food_eaten(&self) -> u32 {
+        self.food_eaten
+    }
+
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
+
+    // Ticks left before time runs out in time attack, or None without a time limit
+    pub fn ticks_left(&self) -> Option<u32> {
+        if self.rules.time_limit == 0 {
+            return None;
+        }
+        let overtime = if self.overtime_granted { self.rules.overtime } else { 0 };
+        Some((self.rules.time_limit + overtime).saturating_sub(self.tick))
+    }
+
+    // Whether running out of time should grant overtime instead of ending the game
+    fn overtime_applies(&self) -> bool {
+        if self.rules.overtime == 0 || self.overtime_granted {
+            return false;
+        }
+        match self.rules.overtime_condition {
+            OvertimeCondition::NearWin { segments } => {
+                self.snake_length() + segments >= self.win_length()
+            }
+            OvertimeCondition::RecentlyAte { ticks } => {
+                self.last_eat_tick.is_some_and(|tick| self.tick - tick <= ticks)
+            }
         }
-        self.tiles[index] = Tile::Food;
+    }
+
+    // Whether the current game has ended, by dying, by winning or by running out of time
+    pub fn game_over(&self) -> bool {
+        !self.snake_alive || self.won || self.timed_out
+    }
+
+    // Record the final score as the highscore if it beats it; announcing the outcome is left to
+    // whoever drives the game, so games played by the autopilot or in replays stay quiet
+    fn end_game(&mut self) {
+        self.highscore = self.highscore.max(self.score);
+    }
+
+    // Whether a tile is in the zone around the snake's head that food shouldn't spawn in
+    fn is_protected(&self, index: TileIndex) -> bool {
+        if self.rules.food_protection_radius > 0 &&
+           self.distance(index, self.snake_head_idx) <= self.rules.food_protection_radius {
+            return true;
+        }
+        let mut ahead = self.snake_head_idx;
+        for _ in 0..self.rules.food_protection_ahead {
+            ahead = self.add_dir_to_index(ahead, self.snake_dir);
+            if ahead == index {
+                return true;
+            }
+        }
+        false
+    }
+
+    // Tiles food can spawn on; the protected zone around the head is avoided unless it covers
+    // all free tiles, and ghost walls are always avoided
+    pub fn food_spawn_tiles(&self) -> Vec<TileIndex> {
+        let free: Vec<TileIndex> =
+            self.floor_tiles().into_iter().filter(|&index| !self.ghost_walls[index]).collect();
+        let unprotected: Vec<TileIndex> =
+            free.iter().cloned().filter(|&index| !self.is_protected(index)).collect();
+        if unprotected.is_empty() { free } else { unprotected }
+    }
+
+    // Use a different strategy for deciding where food spawns, from the next food on; the
+    // strategy is kept when the game is reset
+    pub fn set_spawn_strategy(&mut self, spawn_strategy: BoxedSpawnStrategy) {
+        self.spawn_strategy = spawn_strategy;
+    }
+
+    // Pick a tile to spawn food on with the spawn strategy
+    fn pick_food_tile(&mut self) -> Option<TileIndex> {
+        let mut strategy = ::std::mem::replace(&mut self.spawn_strategy, Box::new(Picking));
+        let index = strategy.pick(self);
+        self.spawn_strategy = strategy;
+        index
+    }
+
+    // Spawn food where the spawn strategy decides, returning where; if the snake fills the
+    // entire playing field, there is nowhere left to put it
+    fn spawn_food(&mut self) -> Option<TileIndex> {
+        let index = self.pick_food_tile();
+        if let Some(index) = index {
+            self.tiles[index] = Tile::Food;
+            self.food_spawn_tick = self.tick;
+        }
+        index
+    }
+
+    // Spawn a new food chain, as much of it as fits on the level, starting over with the first
+    // food in the order
+    fn spawn_food_chain(&mut self) -> Vec<TileIndex> {
+        let mut spawned = Vec::new();
+        for order in 1..self.rules.food_chain + 1 {
+            match self.pick_food_tile() {
+                Some(index) => {
+                    self.tiles[index] = Tile::ChainFood(order);
+                    spawned.push(index);
+                }
+                None => break,
+            }
+        }
+        self.expected_food = 1;
+        self.chain_length = spawned.len() as u32;
+        self.food_spawn_tick = self.tick;
+        spawned
+    }
+
+    // Place in the order of the chain food that has to be eaten next in food chain mode
+    pub fn expected_food(&self) -> u32 {
+        self.expected_food
+    }
+
+    // Top left corners of all 2x2 blocks of floor tiles that don't wrap around the level edges
+    fn floor_blocks(&self) -> Vec<TileIndex> {
+        self.food_spawn_tiles()
+            .into_iter()
+            .filter(|&(y, x)| y + 1 < self.level_height && x + 1 < self.level_width)
+            .filter(|&(y, x)| {
+                [(y, x + 1), (y + 1, x), (y + 1, x + 1)]
+                    .iter()
+                    .all(|&index| self.tiles[index] == Tile::Floor && !self.ghost_walls[index])
+            })
+            .collect()
+    }
+
+    // Spawn big food, unless there already is some on the level
+    fn spawn_big_food(&mut self) {
+        if self.tiles.iter().any(|&tile| matches!(tile, Tile::BigFood { .. })) {
+            return;
+        }
+        let blocks = self.floor_blocks();
+        if let Some(&(y, x)) = self.rng.choose(&blocks) {
+            for &(dy, dx) in &[(0, 0), (0, 1), (1, 0), (1, 1)] {
+                self.tiles[(y + dy, x + dx)] = Tile::BigFood {
+                    top: dy == 0,
+                    left: dx == 0,
+                };
+            }
+        }
+    }
+
+    fn spawn_wall_breaker(&mut self) {
+        let tiles = self.food_spawn_tiles();
+        if let Some(&index) = self.rng.choose(&tiles) {
+            self.tiles[index] = Tile::WallBreaker;
+        }
+    }
+
+    // Score for eating the current food, taking decay since it spawned into account
+    fn food_value(&self) -> u32 {
+        let age = self.tick - self.food_spawn_tick;
+        let decay = self.rules.food_decay.saturating_mul(age);
+        let decayed = self.rules.food_value.saturating_sub(decay);
+        ::std::cmp::max(decayed, self.rules.food_value_floor)
     }
 
     pub fn tiles(&self) -> &TileArray {
         &self.tiles
     }
 
+    pub fn zones(&self) -> &ZoneArray {
+        &self.zones
+    }
+
+    pub fn snake_part_at(&self, index: TileIndex) -> Option<SnakePart> {
+        match self.tiles.get(index) {
+            Some(&Tile::Snake(..)) if index == self.snake_head_idx => Some(SnakePart::Head),
+            Some(&Tile::Snake(..)) if index == self.snake_tail_idx => Some(SnakePart::Tail),
+            Some(&Tile::Snake(..)) => Some(SnakePart::Body),
+            _ => None,
+        }
+    }
+
     fn get_snake_prev(&self, index: TileIndex) -> Result<Direction, String> {
         if let Tile::Snake(Some(prev), _) = self.tiles[index] {
             Ok(prev)
@@ -152,7 +1028,7 @@ impl GameState {
         }
     }
 
-    fn add_dir_to_index(&self, (y, x): TileIndex, dir: Direction) -> TileIndex {
+    pub fn add_dir_to_index(&self, (y, x): TileIndex, dir: Direction) -> TileIndex {
         match dir {
             Direction::Up => ((y + self.level_height - 1) % self.level_height, x),
             Direction::Down => ((y + 1) % self.level_height, x),
@@ -161,10 +1037,141 @@ impl GameState {
         }
     }
 
-    pub fn update(&mut self, input: Option<Direction>) -> Result<(), String> {
-        // Don't do anything if the snake is dead
-        if !self.snake_alive {
-            return Ok(());
+    // Whether moving from a tile in the given direction wraps around to the opposite edge
+    pub fn crosses_edge(&self, (y, x): TileIndex, dir: Direction) -> bool {
+        match dir {
+            Direction::Up => y == 0,
+            Direction::Down => y == self.level_height - 1,
+            Direction::Left => x == 0,
+            Direction::Right => x == self.level_width - 1,
+        }
+    }
+
+    // Manhattan distance between two tiles, taking wrapping around the level edges into account
+    pub fn distance(&self, (y1, x1): TileIndex, (y2, x2): TileIndex) -> usize {
+        let (dy, dx) = (y1.abs_diff(y2), x1.abs_diff(x2));
+        ::std::cmp::min(dy, self.level_height - dy) + ::std::cmp::min(dx, self.level_width - dx)
+    }
+
+    // Whether the snake could move across a tile at all, ignoring wall break charges.  Ghost walls
+    // are ignored as well: they phase in and out as the snake moves, so they only ever block it
+    // for a while.
+    fn is_passable(&self, index: TileIndex) -> bool {
+        match self.tiles[index] {
+            Tile::Floor | Tile::Food | Tile::BigFood { .. } | Tile::ChainFood(_) |
+            Tile::WallBreaker => true,
+            Tile::Wall | Tile::Snake(..) => false,
+        }
+    }
+
+    // Which tiles are reachable from the snake's head through passable tiles, as things stand;
+    // the snake moving out of the way of itself isn't taken into account
+    pub fn reachable_from_head(&self) -> ndarray::Array2<bool> {
+        let mut reachable = ndarray::Array::from_elem(self.tiles.dim(), false);
+        let mut stack = vec![self.snake_head_idx];
+        while let Some(index) = stack.pop() {
+            for &dir in &DIRECTIONS {
+                // Neighbours across the level edges wrap around, as the snake itself does
+                let neighbour = self.add_dir_to_index(index, dir);
+                if !reachable[neighbour] && self.is_passable(neighbour) {
+                    reachable[neighbour] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        reachable
+    }
+
+    // Number of tiles the snake's head can reach, for telling whether it has trapped itself
+    pub fn reachable_area_from_head(&self) -> usize {
+        self.reachable_from_head().iter().filter(|&&reachable| reachable).count()
+    }
+
+    // Whether moving the snake's head onto a tile would end the game
+    fn is_deadly(&self, index: TileIndex) -> bool {
+        if self.is_solid_ghost_wall(index, self.tick + 1) {
+            return self.wall_break_charges == 0;
+        }
+        match self.tiles[index] {
+            Tile::Snake(..) => index != self.snake_tail_idx,
+            Tile::Wall => self.wall_break_charges == 0,
+            Tile::ChainFood(order) => order != self.expected_food,
+            _ => false,
+        }
+    }
+
+    // Scores the autopilot gives the directions the snake can safely move in, higher being
+    // better: the negated distance to the nearest food from the tile the direction leads to.
+    // Keeping the current direction earns a bonus of half a tile so that it wins ties.
+    pub fn suggest_direction_detailed(&self) -> HashMap<Direction, f32> {
+        let food: Vec<TileIndex> = self.tiles
+            .indexed_iter()
+            .filter(|&(_, &tile)| match tile {
+                Tile::Food | Tile::BigFood { .. } => true,
+                Tile::ChainFood(order) => order == self.expected_food,
+                _ => false,
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let mut scores = HashMap::new();
+        for &dir in DIRECTIONS.iter() {
+            if dir == self.snake_dir.reverse() {
+                continue;
+            }
+            let index = self.add_dir_to_index(self.snake_head_idx, dir);
+            if self.is_deadly(index) {
+                continue;
+            }
+            let distance = food.iter().map(|&f| self.distance(index, f)).min().unwrap_or(0);
+            let bonus = if dir == self.snake_dir { 0.5 } else { 0.0 };
+            scores.insert(dir, bonus - distance as f32);
+        }
+        scores
+    }
+
+    // Suggest a direction for the snake, greedily heading towards food while avoiding walls and
+    // its own body
+    pub fn suggest_direction(&self) -> Direction {
+        let scores = self.suggest_direction_detailed();
+        // Go through the directions in a fixed order so that ties are broken the same way every
+        // time; keep going straight if every direction is deadly
+        let mut best: Option<(Direction, f32)> = None;
+        for dir in DIRECTIONS.iter() {
+            if let Some(&score) = scores.get(dir) {
+                if best.is_none_or(|(_, best_score)| score > best_score) {
+                    best = Some((*dir, score));
+                }
+            }
+        }
+        best.map_or(self.snake_dir, |(dir, _)| dir)
+    }
+
+    // Move the tail up by one segment, freeing the tile it was on
+    fn move_tail(&mut self) -> Result<(), String> {
+        let new_snake_tail_idx =
+            self.add_dir_to_index(self.snake_tail_idx,
+                                  self.get_snake_next(self.snake_tail_idx)?);
+        self.tiles[self.snake_tail_idx] = Tile::Floor;
+        self.tiles[new_snake_tail_idx] =
+            Tile::Snake(None, Some(self.get_snake_next(new_snake_tail_idx)?));
+        self.snake_tail_idx = new_snake_tail_idx;
+        Ok(())
+    }
+
+    pub fn update(&mut self, input: Option<Direction>) -> Result<Vec<GameEvent>, String> {
+        let mut events = ::std::mem::take(&mut self.pending_events);
+
+        // Don't do anything if the game is over
+        if self.game_over() {
+            return Ok(events);
+        }
+
+        // Keep the snake in place until the player gets it going, which reversing can't do
+        if !self.started {
+            match input {
+                Some(dir) if dir != self.snake_dir.reverse() => self.started = true,
+                _ => return Ok(events),
+            }
         }
 
         // Handle input
@@ -175,56 +1182,1071 @@ impl GameState {
             }
         }
 
+        // Hold the snake at an edge for one tick before letting it wrap
+        if self.rules.edge_behavior == EdgeBehavior::WarnThenWrap &&
+           self.crosses_edge(self.snake_head_idx, self.snake_dir) &&
+           self.pending_edge != Some(self.snake_dir) {
+            self.pending_edge = Some(self.snake_dir);
+            return Ok(events);
+        }
+        self.pending_edge = None;
+        self.tick += 1;
+
         // Move snake
         let new_snake_head_idx = self.add_dir_to_index(self.snake_head_idx, self.snake_dir);
-        let new_snake_tail_idx =
-            self.add_dir_to_index(self.snake_tail_idx,
-                                  self.get_snake_next(self.snake_tail_idx)?);
         let mut eat_food = false;
+        let mut eat_big_food = false;
+        let mut bite_tail = false;
         // Check for collision
-        match self.tiles[new_snake_head_idx] {
+        // Solid ghost walls act like any other wall, even though the tile itself is floor
+        let new_head_tile = if self.is_solid_ghost_wall(new_snake_head_idx, self.tick) {
+            Tile::Wall
+        } else {
+            self.tiles[new_snake_head_idx]
+        };
+        match new_head_tile {
+            Tile::Snake(..) if new_snake_head_idx == self.snake_tail_idx &&
+                               self.rules.tail_biting &&
+                               self.snake_length() > START_LENGTH => {
+                // Bite off the tail, which the snake can afford as long as it doesn't end up
+                // shorter than it started out
+                bite_tail = true;
+                self.score = self.score.saturating_sub(self.rules.tail_bite_penalty);
+                events.push(GameEvent::BitTail { index: new_snake_head_idx });
+            }
+            Tile::Snake(..) if new_snake_head_idx == self.snake_tail_idx => {
+                // The tail moves out of the way on this tick, so the head can follow right
+                // behind it
+            }
+            Tile::Wall if self.wall_break_charges > 0 => {
+                // Break through the wall, using up a charge; a ghost wall broken through is gone
+                // for good rather than coming back once it phases in again
+                self.wall_break_charges -= 1;
+                self.tiles[new_snake_head_idx] = Tile::Floor;
+                self.ghost_walls[new_snake_head_idx] = false;
+                self.count_free_tiles();
+                events.push(GameEvent::BrokeWall { index: new_snake_head_idx });
+            }
             Tile::Wall | Tile::Snake(..) => {
                 // New head collides with wall or snake, so game over
                 self.snake_alive = false;
-                println!("Game over!");
-                println!("Your score: {}", self.score);
-                if self.score > self.highscore {
-                    println!("*** New highscore! ***");
-                    self.highscore = self.score;
-                } else {
-                    println!("Highscore: {}", self.highscore);
-                }
-                return Ok(());
+                self.end_game();
+                events.push(GameEvent::Died { index: self.snake_head_idx });
+                return Ok(events);
             }
             Tile::Food => {
                 // New head collides with food, so eat the food
                 eat_food = true;
             }
+            Tile::ChainFood(order) if order == self.expected_food => {
+                // Eat the next food in the chain, moving on to the one after it
+                eat_food = true;
+                self.expected_food += 1;
+            }
+            Tile::ChainFood(_) => {
+                // Eating food out of order breaks the chain, so game over
+                self.snake_alive = false;
+                self.end_game();
+                events.push(GameEvent::Died { index: self.snake_head_idx });
+                return Ok(events);
+            }
+            Tile::BigFood { .. } => {
+                // Take a bite out of the big food, leaving the rest of it in place
+                eat_big_food = true;
+            }
+            Tile::WallBreaker => {
+                self.wall_break_charges += 1;
+                events.push(GameEvent::GotWallBreaker { index: new_snake_head_idx });
+            }
             Tile::Floor => {} // No collision
         }
+        // Move snake tail unless the snake grows, before moving the head into a tile the tail
+        // may be vacating.  Snakes at the length cap keep scoring but stop growing.
+        let at_max_length = self.rules.max_length > 0 &&
+                            self.snake_length() >= self.rules.max_length;
+        if !(eat_food || eat_big_food) || at_max_length {
+            self.move_tail()?;
+        }
+        // Biting off the tail takes another segment off the end, shrinking the snake by one
+        if bite_tail {
+            self.move_tail()?;
+        }
         // Move snake head
         self.tiles[self.snake_head_idx] =
             Tile::Snake(Some(self.get_snake_prev(self.snake_head_idx)?),
                         Some(self.snake_dir));
         self.tiles[new_snake_head_idx] = Tile::Snake(Some(self.snake_dir.reverse()), None);
         self.snake_head_idx = new_snake_head_idx;
-        // Spawn new food or move snake tail
+        // Spawn new food
+        if eat_food || eat_big_food {
+            self.last_eat_tick = Some(self.tick);
+            self.food_eaten += 1;
+        }
         if eat_food {
-            self.score += 10;
-            self.spawn_food();
-        } else {
-            self.tiles[self.snake_tail_idx] = Tile::Floor;
-            self.tiles[new_snake_tail_idx] =
-                Tile::Snake(None, Some(self.get_snake_next(new_snake_tail_idx)?));
-            self.snake_tail_idx = new_snake_tail_idx;
+            let score = self.food_value();
+            self.score += score;
+            events.push(GameEvent::AteFood {
+                            index: new_snake_head_idx,
+                            score: score,
+                        });
+            // A food chain is only replaced once all of it has been eaten
+            let spawned = if self.rules.food_chain == 0 {
+                self.spawn_food().into_iter().collect()
+            } else if self.expected_food > self.chain_length {
+                self.spawn_food_chain()
+            } else {
+                Vec::new()
+            };
+            for index in spawned {
+                events.push(GameEvent::SpawnedFood { index: index });
+            }
+            if self.rng.gen_range(0, 100) < self.rules.big_food_chance {
+                self.spawn_big_food();
+            }
+            if self.rng.gen_range(0, 100) < self.rules.wall_breaker_chance {
+                self.spawn_wall_breaker();
+            }
+        }
+        if eat_big_food {
+            self.score += self.rules.big_food_bite_value;
+            events.push(GameEvent::AteFood {
+                            index: new_snake_head_idx,
+                            score: self.rules.big_food_bite_value,
+                        });
+        }
+        // Ink the zone under the new head
+        if self.zones[new_snake_head_idx] == Zone::Uninked {
+            self.zones[new_snake_head_idx] = Zone::Inked;
+            self.score += self.rules.ink_zone_bonus;
+            events.push(GameEvent::InkedZone {
+                            index: new_snake_head_idx,
+                            score: self.rules.ink_zone_bonus,
+                        });
         }
 
-        Ok(())
+        self.painted[new_snake_head_idx] = true;
+
+        let all_zones_inked = self.zones.iter().any(|&zone| zone == Zone::Inked) &&
+                              self.zones.iter().all(|&zone| zone != Zone::Uninked);
+        if self.snake_length() >= self.win_length() || all_zones_inked || self.painted_enough() {
+            self.won = true;
+            self.end_game();
+            events.push(GameEvent::Won);
+        } else if self.ticks_left() == Some(0) {
+            if self.overtime_applies() {
+                self.overtime_granted = true;
+                events.push(GameEvent::Overtime { ticks: self.rules.overtime });
+            } else {
+                self.timed_out = true;
+                self.end_game();
+                events.push(GameEvent::TimeUp);
+            }
+        }
+
+        Ok(events)
+    }
+}
+
+// Configuration for building a new game state, with defaults matching GameState::default
+#[derive(Clone, Debug)]
+pub struct GameStateBuilder {
+    level_size: (usize, usize),
+    highscore: u32,
+    rules: Rules,
+    walls: bool,
+    level_seed: Option<u32>,
+}
+
+impl GameStateBuilder {
+    pub fn new() -> Self {
+        GameStateBuilder {
+            level_size: DEFAULT_LEVEL_SIZE,
+            highscore: 0,
+            rules: Rules::default(),
+            walls: true,
+            level_seed: None,
+        }
+    }
+
+    pub fn level_size(mut self, level_width: usize, level_height: usize) -> Self {
+        self.level_size = (level_width, level_height);
+        self
+    }
+
+    pub fn highscore(mut self, highscore: u32) -> Self {
+        self.highscore = highscore;
+        self
+    }
+
+    pub fn rules(mut self, rules: Rules) -> Self {
+        self.rules = rules;
+        self
+    }
+
+    pub fn walls(mut self, walls: bool) -> Self {
+        self.walls = walls;
+        self
+    }
+
+    // Seed for the level layout; without one, a random seed is used
+    pub fn level_seed(mut self, level_seed: u32) -> Self {
+        self.level_seed = Some(level_seed);
+        self
+    }
+
+    pub fn build(self) -> Result<GameState, String> {
+        let (level_width, level_height) = self.level_size;
+        if level_width < MIN_LEVEL_SIZE.0 || level_height < MIN_LEVEL_SIZE.1 {
+            return Err(format!("The level must be at least {}x{} tiles",
+                               MIN_LEVEL_SIZE.0,
+                               MIN_LEVEL_SIZE.1));
+        }
+        let level_seed = self.level_seed.unwrap_or_else(|| rand::thread_rng().gen());
+        Ok(GameState::generate(level_width,
+                               level_height,
+                               self.highscore,
+                               self.rules,
+                               self.walls,
+                               level_seed,
+                               default_spawn_strategy()))
+    }
+}
+
+impl Default for GameStateBuilder {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 impl Default for GameState {
     fn default() -> Self {
-        Self::new(40, 30, 0)
+        Self::new(DEFAULT_LEVEL_SIZE.0, DEFAULT_LEVEL_SIZE.1, 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snake_part_at_classifies_the_default_snake() {
+        let game_state = GameState::new(40, 30, 0);
+        assert_eq!(game_state.snake_part_at((3, 5)), Some(SnakePart::Head));
+        assert_eq!(game_state.snake_part_at((3, 4)), Some(SnakePart::Body));
+        assert_eq!(game_state.snake_part_at((3, 3)), Some(SnakePart::Tail));
+    }
+
+    #[test]
+    fn snake_part_at_is_none_off_the_snake() {
+        let game_state = GameState::new(40, 30, 0);
+        assert_eq!(game_state.snake_part_at((0, 0)), None);
+        assert_eq!(game_state.snake_part_at((30, 40)), None);
+    }
+
+    #[test]
+    fn warn_then_wrap_holds_the_snake_at_the_edge_for_one_tick() {
+        let rules = Rules { edge_behavior: EdgeBehavior::WarnThenWrap, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).walls(false).build().unwrap();
+        while game_state.snake_head() != (3, 39) {
+            game_state.update(None).unwrap();
+        }
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.snake_head(), (3, 39));
+        assert!(game_state.snake_alive());
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.snake_head(), (3, 0));
+    }
+
+    #[test]
+    fn warn_then_wrap_warns_again_when_the_edge_is_approached_again() {
+        let rules = Rules { edge_behavior: EdgeBehavior::WarnThenWrap, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).walls(false).build().unwrap();
+        while game_state.snake_head() != (3, 39) {
+            game_state.update(None).unwrap();
+        }
+        game_state.update(None).unwrap();
+        // Turning away along the edge forgets the warning
+        game_state.update(Some(Direction::Down)).unwrap();
+        assert_eq!(game_state.snake_head(), (4, 39));
+        game_state.update(Some(Direction::Right)).unwrap();
+        assert_eq!(game_state.snake_head(), (4, 39));
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.snake_head(), (4, 0));
+    }
+
+    #[test]
+    fn wrap_crosses_the_edge_straight_away() {
+        let mut game_state = GameStateBuilder::new().walls(false).build().unwrap();
+        while game_state.snake_head() != (3, 39) {
+            game_state.update(None).unwrap();
+        }
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.snake_head(), (3, 0));
+    }
+
+    // Take the randomly spawned food off the board, so that tests can place their own
+    fn clear_food(game_state: &mut GameState) {
+        for tile in game_state.tiles.iter_mut() {
+            match *tile {
+                Tile::Food | Tile::ChainFood(_) => *tile = Tile::Floor,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn food_eaten_early_scores_more_than_food_eaten_late() {
+        let rules = Rules { food_decay: 2, food_value_floor: 3, ..Rules::default() };
+        let mut early = GameStateBuilder::new().rules(rules.clone()).build().unwrap();
+        let mut late = GameStateBuilder::new().rules(rules).build().unwrap();
+        clear_food(&mut early);
+        clear_food(&mut late);
+        early.tiles[(3, 6)] = Tile::Food;
+        late.tiles[(3, 10)] = Tile::Food;
+        early.update(None).unwrap();
+        assert_eq!(early.score(), 8);
+        for _ in 0..5 {
+            late.update(None).unwrap();
+        }
+        assert_eq!(late.score(), 3);
+    }
+
+    #[test]
+    fn food_keeps_its_value_without_decay() {
+        let mut game_state = GameState::new(40, 30, 0);
+        clear_food(&mut game_state);
+        game_state.tiles[(3, 10)] = Tile::Food;
+        for _ in 0..5 {
+            game_state.update(None).unwrap();
+        }
+        assert_eq!(game_state.score(), 10);
+    }
+
+    #[test]
+    fn food_never_spawns_in_the_protected_zone() {
+        let rules = Rules {
+            food_protection_radius: 4,
+            food_protection_ahead: 10,
+            ..Rules::default()
+        };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        for _ in 0..200 {
+            let index = game_state.spawn_food().unwrap();
+            assert!(game_state.distance(index, game_state.snake_head()) > 4);
+            assert!(!(index.0 == 3 && index.1 > 5 && index.1 <= 15));
+            game_state.tiles[index] = Tile::Floor;
+        }
+    }
+
+    #[test]
+    fn food_spawns_in_the_protected_zone_when_nothing_else_is_free() {
+        let rules = Rules { food_protection_radius: 100, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        assert!(game_state.spawn_food().is_some());
+    }
+
+    #[test]
+    fn reaching_the_win_length_wins_on_a_small_board() {
+        let rules = Rules { win_fill_percent: 10, ..Rules::default() };
+        let mut game_state =
+            GameStateBuilder::new().level_size(10, 7).rules(rules).build().unwrap();
+        // 10% of the 8x5 tiles inside the walls
+        assert_eq!(game_state.win_length(), 4);
+        let ahead = game_state.add_dir_to_index(game_state.snake_head(), game_state.snake_dir());
+        game_state.tiles[ahead] = Tile::Food;
+        let events = game_state.update(None).unwrap();
+        assert!(game_state.won());
+        assert!(events.contains(&GameEvent::Won));
+    }
+
+    #[test]
+    fn falling_short_of_the_win_length_doesnt_win() {
+        let rules = Rules { win_fill_percent: 20, ..Rules::default() };
+        let mut game_state =
+            GameStateBuilder::new().level_size(10, 7).rules(rules).build().unwrap();
+        let ahead = game_state.add_dir_to_index(game_state.snake_head(), game_state.snake_dir());
+        game_state.tiles[ahead] = Tile::Food;
+        game_state.update(None).unwrap();
+        assert!(!game_state.won());
+    }
+
+    // Replace the snake with one curled up in a square, so that its head sits right behind its
+    // tail, and clear away the food so nothing gets eaten
+    fn curl_snake(game_state: &mut GameState, tail: TileIndex) {
+        clear_food(game_state);
+        for tile in game_state.tiles.iter_mut() {
+            if let Tile::Snake(..) = *tile {
+                *tile = Tile::Floor;
+            }
+        }
+        let body = game_state.add_dir_to_index(tail, Direction::Right);
+        let corner = game_state.add_dir_to_index(body, Direction::Down);
+        let head = game_state.add_dir_to_index(corner, Direction::Left);
+        game_state.tiles[tail] = Tile::Snake(None, Some(Direction::Right));
+        game_state.tiles[body] = Tile::Snake(Some(Direction::Left), Some(Direction::Down));
+        game_state.tiles[corner] = Tile::Snake(Some(Direction::Up), Some(Direction::Left));
+        game_state.tiles[head] = Tile::Snake(Some(Direction::Right), None);
+        game_state.snake_tail_idx = tail;
+        game_state.snake_head_idx = head;
+        game_state.snake_dir = Direction::Left;
+    }
+
+    #[test]
+    fn head_can_follow_right_behind_the_tail() {
+        let mut game_state = GameState::new(40, 30, 0);
+        curl_snake(&mut game_state, (5, 5));
+        for &dir in &[Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            let tail = game_state.snake_tail_idx;
+            game_state.update(Some(dir)).unwrap();
+            assert!(game_state.snake_alive());
+            assert_eq!(game_state.snake_head(), tail);
+            assert_eq!(game_state.snake_length(), 4);
+        }
+    }
+
+    #[test]
+    fn head_can_follow_the_tail_across_the_edge() {
+        let mut game_state = GameStateBuilder::new().walls(false).build().unwrap();
+        curl_snake(&mut game_state, (29, 39));
+        game_state.update(Some(Direction::Up)).unwrap();
+        assert!(game_state.snake_alive());
+        assert_eq!(game_state.snake_head(), (29, 39));
+    }
+
+    #[test]
+    fn entering_an_inked_zone_awards_nothing() {
+        let rules = Rules { ink_zone_bonus: 5, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        clear_food(&mut game_state);
+        game_state.zones[(3, 6)] = Zone::Uninked;
+        game_state.zones[(3, 7)] = Zone::Inked;
+        game_state.zones[(20, 20)] = Zone::Uninked;
+        let events = game_state.update(None).unwrap();
+        assert!(events.contains(&GameEvent::InkedZone { index: (3, 6), score: 5 }));
+        assert_eq!(game_state.zones[(3, 6)], Zone::Inked);
+        let events = game_state.update(None).unwrap();
+        assert!(events.is_empty());
+        assert_eq!(game_state.score(), 5);
+    }
+
+    #[test]
+    fn inking_every_zone_wins() {
+        let mut game_state = GameState::new(40, 30, 0);
+        clear_food(&mut game_state);
+        game_state.zones[(3, 6)] = Zone::Uninked;
+        game_state.zones[(3, 7)] = Zone::Uninked;
+        game_state.update(None).unwrap();
+        assert!(!game_state.won());
+        let events = game_state.update(None).unwrap();
+        assert!(events.contains(&GameEvent::Won));
+        assert!(game_state.won());
+    }
+
+    #[test]
+    fn old_saves_are_fitted_to_their_level() {
+        let game_state = GameStateBuilder::new().level_size(20, 15).build().unwrap();
+        let mut saved = ::serde_json::to_value(&game_state).unwrap();
+        if let ::serde_json::Value::Object(ref mut fields) = saved {
+            for &field in &["level_width", "level_height", "zones", "ghost_walls", "painted"] {
+                fields.remove(field);
+            }
+        }
+        let mut loaded = ::serde_json::from_value::<GameState>(saved).unwrap();
+        loaded.fit_to_level();
+        assert_eq!(loaded.level_size(), (20, 15));
+        assert_eq!(loaded.zones().dim(), (15, 20));
+        assert_eq!(loaded.ghost_walls().dim(), (15, 20));
+        assert_eq!(loaded.painted().dim(), (15, 20));
+        assert!(loaded.tiles() == game_state.tiles());
+        loaded.update(None).unwrap();
+        assert!(loaded.snake_alive());
+    }
+
+    #[test]
+    fn old_saves_keep_their_border_on_reset() {
+        for &walls in &[false, true] {
+            let game_state = GameStateBuilder::new().walls(walls).build().unwrap();
+            let mut saved = ::serde_json::to_value(&game_state).unwrap();
+            if let ::serde_json::Value::Object(ref mut fields) = saved {
+                fields.remove("walls");
+            }
+            let mut loaded = ::serde_json::from_value::<GameState>(saved).unwrap();
+            loaded.fit_to_level();
+            assert_eq!(loaded.walls(), walls);
+            loaded.reset();
+            assert_eq!(loaded.walls(), walls);
+            assert_eq!(loaded.tiles()[(0, 0)] == Tile::Wall, walls);
+        }
+    }
+
+    #[test]
+    fn a_charge_breaks_through_a_wall_and_the_next_wall_kills() {
+        let mut game_state = GameState::new(40, 30, 0);
+        game_state.wall_break_charges = 1;
+        let free_tile_count = game_state.free_tile_count;
+        while game_state.snake_head() != (3, 38) {
+            game_state.update(None).unwrap();
+        }
+        let events = game_state.update(None).unwrap();
+        assert!(events.contains(&GameEvent::BrokeWall { index: (3, 39) }));
+        assert!(game_state.snake_alive());
+        assert_eq!(game_state.snake_head(), (3, 39));
+        assert_eq!(game_state.wall_break_charges(), 0);
+        assert_eq!(game_state.free_tile_count, free_tile_count + 1);
+        // The border wall on the other side is still there, and there's no charge left for it
+        let events = game_state.update(None).unwrap();
+        assert!(events.contains(&GameEvent::Died { index: (3, 39) }));
+        assert!(!game_state.snake_alive());
+    }
+
+    #[test]
+    fn win_progress_is_the_fraction_of_the_win_length_reached() {
+        let rules = Rules { win_fill_percent: 20, ..Rules::default() };
+        let mut game_state =
+            GameStateBuilder::new().level_size(10, 7).rules(rules).build().unwrap();
+        assert_eq!(game_state.win_length(), 8);
+        assert_eq!(game_state.win_progress(), 0.375);
+        let ahead = game_state.add_dir_to_index(game_state.snake_head(), game_state.snake_dir());
+        game_state.tiles[ahead] = Tile::Food;
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.win_progress(), 0.5);
+    }
+
+    #[test]
+    fn win_progress_is_full_once_won() {
+        let rules = Rules { win_fill_percent: 10, ..Rules::default() };
+        let mut game_state =
+            GameStateBuilder::new().level_size(10, 7).rules(rules).build().unwrap();
+        let ahead = game_state.add_dir_to_index(game_state.snake_head(), game_state.snake_dir());
+        game_state.tiles[ahead] = Tile::Food;
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.win_progress(), 1.0);
+    }
+
+    // Number of open tiles that are walled in on all four sides
+    fn count_pockets(game_state: &GameState) -> usize {
+        game_state.tiles
+            .indexed_iter()
+            .filter(|&(index, &tile)| {
+                tile != Tile::Wall &&
+                DIRECTIONS.iter().all(|&dir| {
+                    game_state.tiles[game_state.add_dir_to_index(index, dir)] == Tile::Wall
+                })
+            })
+            .count()
+    }
+
+    #[test]
+    fn filling_pockets_leaves_no_enclosed_floor() {
+        let rules = Rules { obstacles: 300, fill_pockets: true, ..Rules::default() };
+        for level_seed in 0..20 {
+            let builder = GameStateBuilder::new().rules(rules.clone()).level_seed(level_seed);
+            assert_eq!(count_pockets(&builder.build().unwrap()), 0, "level seed {}", level_seed);
+        }
+    }
+
+    #[test]
+    fn dense_obstacles_leave_pockets_unless_filled() {
+        let rules = Rules { obstacles: 300, fill_pockets: false, ..Rules::default() };
+        let pockets = (0..20)
+            .map(|level_seed| {
+                let builder = GameStateBuilder::new().rules(rules.clone()).level_seed(level_seed);
+                count_pockets(&builder.build().unwrap())
+            })
+            .sum::<usize>();
+        assert!(pockets > 0);
+    }
+
+    #[test]
+    fn the_first_update_reports_the_food_the_level_started_with() {
+        let mut game_state = GameState::new(40, 30, 0);
+        let food = game_state.tiles.indexed_iter().find(|&(_, &tile)| tile == Tile::Food);
+        let (food, _) = food.unwrap();
+        let events = game_state.update(None).unwrap();
+        assert_eq!(events[0], GameEvent::SpawnedFood { index: food });
+        let events = game_state.update(None).unwrap();
+        assert!(!events.contains(&GameEvent::SpawnedFood { index: food }));
+    }
+
+    #[test]
+    fn eat_observers_are_called_for_each_food_eaten() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let eaten = Rc::new(Cell::new(0));
+        let mut observers = Observers::default();
+        let counter = eaten.clone();
+        observers.on_eat(move |_| counter.set(counter.get() + 1));
+        let mut game_state = GameState::new(40, 30, 0);
+        clear_food(&mut game_state);
+        game_state.tiles[(3, 6)] = Tile::Food;
+        game_state.tiles[(3, 7)] = Tile::Food;
+        for _ in 0..2 {
+            let events = game_state.update(None).unwrap();
+            observers.notify(&events);
+        }
+        assert_eq!(eaten.get(), 2);
+    }
+
+    // Whether the snake can move straight ahead through its start zone without running into
+    // anything
+    fn has_clear_start(game_state: &GameState) -> bool {
+        let mut index = game_state.snake_head();
+        (0..START_ZONE_LENGTH).all(|_| {
+            index = game_state.add_dir_to_index(index, game_state.snake_dir());
+            game_state.tiles[index] != Tile::Wall
+        })
+    }
+
+    #[test]
+    fn new_layouts_on_reset_differ_and_start_clear() {
+        let rules = Rules { obstacles: 200, new_layout_on_reset: true, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        game_state.reset();
+        let first = game_state.clone();
+        game_state.reset();
+        assert!(first.tiles() != game_state.tiles());
+        for game_state in &[first, game_state] {
+            assert_eq!(game_state.snake_length(), START_LENGTH);
+            assert!(has_clear_start(game_state));
+        }
+    }
+
+    #[test]
+    fn every_bite_of_big_food_scores_and_grows_the_snake() {
+        let mut game_state = GameState::new(40, 30, 0);
+        clear_food(&mut game_state);
+        for &(dy, dx) in &[(0, 0), (0, 1), (1, 0), (1, 1)] {
+            game_state.tiles[(3 + dy, 6 + dx)] = Tile::BigFood { top: dy == 0, left: dx == 0 };
+        }
+        let bites = [None, None, Some(Direction::Down), Some(Direction::Left)];
+        for (i, &input) in bites.iter().enumerate() {
+            let events = game_state.update(input).unwrap();
+            let head = game_state.snake_head();
+            assert!(events.contains(&GameEvent::AteFood { index: head, score: 5 }));
+            assert_eq!(game_state.snake_length(), START_LENGTH + i + 1);
+        }
+        assert_eq!(game_state.score(), 20);
+        assert!(game_state.tiles.iter().all(|&tile| !matches!(tile, Tile::BigFood { .. })));
+    }
+
+    #[test]
+    fn immediate_start_moves_the_snake_on_the_first_update() {
+        let mut game_state = GameState::new(40, 30, 0);
+        assert!(game_state.started());
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.snake_head(), (3, 6));
+    }
+
+    #[test]
+    fn waiting_for_input_keeps_the_snake_still_until_a_direction_arrives() {
+        let rules = Rules { start_mode: StartMode::WaitForInput, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        for &input in &[None, None, Some(Direction::Left)] {
+            game_state.update(input).unwrap();
+            assert!(!game_state.started());
+            assert_eq!(game_state.snake_head(), (3, 5));
+            assert_eq!(game_state.tick(), 0);
+        }
+        game_state.update(Some(Direction::Up)).unwrap();
+        assert!(game_state.started());
+        assert_eq!(game_state.snake_head(), (2, 5));
+    }
+
+    #[test]
+    fn waiting_for_input_starts_on_the_direction_the_snake_faces() {
+        let rules = Rules { start_mode: StartMode::WaitForInput, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        game_state.update(Some(Direction::Right)).unwrap();
+        assert!(game_state.started());
+        assert_eq!(game_state.snake_head(), (3, 6));
+    }
+
+    // Events of a time attack game on a small board, two ticks in when time runs out
+    fn events_as_time_runs_out(rules: Rules, eat: bool) -> Vec<GameEvent> {
+        let rules = Rules { win_fill_percent: 20, time_limit: 2, overtime: 3, ..rules };
+        let mut game_state =
+            GameStateBuilder::new().level_size(10, 7).rules(rules).build().unwrap();
+        clear_food(&mut game_state);
+        if eat {
+            let ahead =
+                game_state.add_dir_to_index(game_state.snake_head(), game_state.snake_dir());
+            game_state.tiles[ahead] = Tile::Food;
+        }
+        game_state.update(None).unwrap();
+        // Food spawned in place of what was eaten could land right ahead of the snake again
+        clear_food(&mut game_state);
+        game_state.update(None).unwrap()
+    }
+
+    #[test]
+    fn overtime_is_granted_only_near_a_win() {
+        // The snake is 5 segments short of the 8 it needs to win
+        let near = OvertimeCondition::NearWin { segments: 5 };
+        let events = events_as_time_runs_out(Rules { overtime_condition: near, ..Rules::default() },
+                                             false);
+        assert_eq!(events, vec![GameEvent::Overtime { ticks: 3 }]);
+        let far = OvertimeCondition::NearWin { segments: 4 };
+        let events = events_as_time_runs_out(Rules { overtime_condition: far, ..Rules::default() },
+                                             false);
+        assert_eq!(events, vec![GameEvent::TimeUp]);
+    }
+
+    #[test]
+    fn overtime_is_granted_only_after_eating_recently() {
+        let rules = Rules {
+            overtime_condition: OvertimeCondition::RecentlyAte { ticks: 1 },
+            ..Rules::default()
+        };
+        let events = events_as_time_runs_out(rules.clone(), true);
+        assert_eq!(events, vec![GameEvent::Overtime { ticks: 3 }]);
+        let events = events_as_time_runs_out(rules, false);
+        assert_eq!(events, vec![GameEvent::TimeUp]);
+    }
+
+    #[test]
+    fn snake_bounds_of_a_compact_snake() {
+        let game_state = GameState::new(40, 30, 0);
+        assert_eq!(game_state.snake_bounds(), ((3, 3), (3, 5)));
+    }
+
+    #[test]
+    fn snake_bounds_of_a_snake_spanning_the_board() {
+        let mut game_state = GameState::new(40, 30, 0);
+        clear_food(&mut game_state);
+        for x in 6..36 {
+            game_state.tiles[(3, x)] = Tile::Food;
+        }
+        for y in 4..26 {
+            game_state.tiles[(y, 35)] = Tile::Food;
+        }
+        for _ in 6..36 {
+            game_state.update(None).unwrap();
+        }
+        game_state.update(Some(Direction::Down)).unwrap();
+        for _ in 5..26 {
+            game_state.update(None).unwrap();
+        }
+        assert!(game_state.snake_alive());
+        assert_eq!(game_state.snake_bounds(), ((3, 3), (25, 35)));
+    }
+
+    #[test]
+    fn snake_bounds_across_a_wrapped_edge_span_the_whole_board() {
+        let mut game_state = GameStateBuilder::new().walls(false).build().unwrap();
+        curl_snake(&mut game_state, (29, 39));
+        assert_eq!(game_state.snake_bounds(), ((0, 0), (29, 39)));
+    }
+
+    #[test]
+    fn biting_the_tail_shrinks_the_snake_at_a_cost() {
+        let rules = Rules { tail_biting: true, tail_bite_penalty: 4, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        curl_snake(&mut game_state, (5, 5));
+        game_state.score = 10;
+        let events = game_state.update(Some(Direction::Up)).unwrap();
+        assert!(events.contains(&GameEvent::BitTail { index: (5, 5) }));
+        assert!(game_state.snake_alive());
+        assert_eq!(game_state.snake_head(), (5, 5));
+        assert_eq!(game_state.snake_length(), 3);
+        assert_eq!(game_state.score(), 6);
+    }
+
+    #[test]
+    fn biting_the_body_is_still_deadly() {
+        let rules = Rules { tail_biting: true, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        curl_snake(&mut game_state, (5, 5));
+        // Add a new tail behind the old one, so that the head is behind a body segment instead
+        game_state.tiles[(5, 4)] = Tile::Snake(None, Some(Direction::Right));
+        game_state.tiles[(5, 5)] = Tile::Snake(Some(Direction::Left), Some(Direction::Right));
+        game_state.snake_tail_idx = (5, 4);
+        let events = game_state.update(Some(Direction::Up)).unwrap();
+        assert!(events.contains(&GameEvent::Died { index: (6, 5) }));
+        assert!(!game_state.snake_alive());
+    }
+
+    #[test]
+    fn rebuilding_keeps_the_score_on_the_new_level() {
+        let mut game_state = GameState::new(40, 30, 120);
+        game_state.score = 25;
+        game_state.rebuild(GameStateBuilder::new().level_size(20, 15).walls(false)).unwrap();
+        assert_eq!(game_state.level_size(), (20, 15));
+        assert_eq!(game_state.tiles().dim(), (15, 20));
+        assert!(!game_state.walls());
+        assert_eq!(game_state.score(), 25);
+        assert_eq!(game_state.highscore(), 120);
+        assert_eq!(game_state.snake_length(), START_LENGTH);
+        assert!(has_clear_start(&game_state));
+        game_state.update(None).unwrap();
+        assert!(game_state.snake_alive());
+    }
+
+    #[test]
+    fn resetting_keeps_the_walls_as_they_were() {
+        let mut game_state = GameStateBuilder::new().walls(false).build().unwrap();
+        game_state.reset();
+        assert!(!game_state.walls());
+        assert!(game_state.tiles().iter().all(|&tile| tile != Tile::Wall));
+        let mut game_state = GameState::new(40, 30, 0);
+        game_state.reset();
+        assert!(game_state.walls());
+        assert_eq!(game_state.tiles()[(0, 0)], Tile::Wall);
+    }
+
+    #[test]
+    fn a_cramped_level_moves_the_snake_to_a_safe_start() {
+        let mut game_state = GameState::new(10, 7, 0);
+        // The usual start would run the snake into the right wall within a few ticks
+        assert!(game_state.snake_tail_idx != (3, 3));
+        assert_eq!(game_state.snake_length(), START_LENGTH);
+        assert!(has_clear_start(&game_state));
+        for tile in game_state.tiles.iter_mut() {
+            if let Tile::Snake(..) = *tile {
+                *tile = Tile::Floor;
+            }
+        }
+        let (tail, dir) = game_state.find_safe_spawn(START_LENGTH).unwrap();
+        assert!(game_state.is_safe_spawn(tail, dir, START_LENGTH));
+    }
+
+    #[test]
+    fn a_level_too_cramped_for_any_safe_start_has_none() {
+        let game_state = GameState::new(7, 5, 0);
+        assert_eq!(game_state.find_safe_spawn(START_LENGTH), None);
+        assert_eq!(game_state.find_safe_spawn(1), None);
+    }
+
+    #[test]
+    fn the_head_can_reach_all_of_an_open_board() {
+        let game_state = GameStateBuilder::new().walls(false).build().unwrap();
+        assert_eq!(game_state.reachable_area_from_head(), 40 * 30 - START_LENGTH);
+        let game_state = GameState::new(40, 30, 0);
+        assert_eq!(game_state.reachable_area_from_head(), 38 * 28 - START_LENGTH);
+    }
+
+    #[test]
+    fn a_walled_off_head_reaches_little() {
+        let mut game_state = GameState::new(40, 30, 0);
+        // Wall in the head along with the two tiles ahead of it
+        for &index in &[(2, 5), (4, 5), (2, 6), (4, 6), (2, 7), (4, 7), (3, 8)] {
+            game_state.tiles[index] = Tile::Wall;
+        }
+        for &index in &[(3, 6), (3, 7)] {
+            game_state.tiles[index] = Tile::Floor;
+        }
+        assert_eq!(game_state.reachable_area_from_head(), 2);
+    }
+
+    #[test]
+    fn reachable_area_wraps_around_the_level_edges() {
+        let mut game_state = GameStateBuilder::new().walls(false).build().unwrap();
+        // A wall across the whole level only splits it when edges don't wrap
+        for y in 0..30 {
+            game_state.tiles[(y, 20)] = Tile::Wall;
+        }
+        assert_eq!(game_state.reachable_area_from_head(), 39 * 30 - START_LENGTH);
+    }
+
+    #[test]
+    fn eating_at_the_length_cap_scores_without_growing() {
+        let rules = Rules { max_length: 4, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        clear_food(&mut game_state);
+        game_state.tiles[(3, 6)] = Tile::Food;
+        game_state.tiles[(3, 7)] = Tile::Food;
+        game_state.update(None).unwrap();
+        assert_eq!(game_state.snake_length(), 4);
+        let score = game_state.score();
+        let events = game_state.update(None).unwrap();
+        assert!(events.iter().any(|event| matches!(*event, GameEvent::AteFood { .. })));
+        assert!(game_state.score() > score);
+        assert_eq!(game_state.snake_length(), 4);
+        assert_eq!(game_state.snake_tail_idx, (3, 4));
+    }
+
+    #[test]
+    fn reachable_tiles_on_a_small_walled_board() {
+        let game_state = GameState::new(7, 5, 0);
+        // Too cramped for a safe start, so the snake is where it usually starts
+        assert_eq!(game_state.snake_segments(), vec![(3, 3), (3, 4), (3, 5)]);
+        let reachable = game_state.reachable_from_head();
+        for ((y, x), &reached) in reachable.indexed_iter() {
+            let inside = (1..4).contains(&y) && (1..6).contains(&x);
+            let snake = y == 3 && (3..6).contains(&x);
+            assert_eq!(reached, inside && !snake, "tile {:?}", (y, x));
+        }
+    }
+
+    // The default game with food cleared away and a ghost wall right ahead of the snake
+    fn ghost_wall_ahead(ghost_wall_period: u32) -> GameState {
+        let rules = Rules { ghost_wall_period: ghost_wall_period, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        clear_food(&mut game_state);
+        game_state.ghost_walls[(3, 6)] = true;
+        game_state
+    }
+
+    #[test]
+    fn solid_ghost_walls_are_deadly() {
+        let mut game_state = ghost_wall_ahead(20);
+        assert!(game_state.ghost_walls_solid());
+        game_state.update(None).unwrap();
+        assert!(!game_state.snake_alive());
+    }
+
+    #[test]
+    fn phased_out_ghost_walls_can_be_crossed() {
+        // With a period of one tick, ghost walls are already phased out for the first move
+        let mut game_state = ghost_wall_ahead(1);
+        assert!(!game_state.ghost_walls_solid());
+        game_state.update(None).unwrap();
+        assert!(game_state.snake_alive());
+        assert_eq!(game_state.snake_head(), (3, 6));
+    }
+
+    #[test]
+    fn breaking_through_a_ghost_wall_removes_it() {
+        let mut game_state = ghost_wall_ahead(20);
+        game_state.wall_break_charges = 1;
+        let events = game_state.update(None).unwrap();
+        assert!(events.contains(&GameEvent::BrokeWall { index: (3, 6) }));
+        assert!(game_state.snake_alive());
+        assert_eq!(game_state.wall_break_charges(), 0);
+        assert!(!game_state.ghost_walls()[(3, 6)]);
+    }
+
+    // The default game with its only food below the snake's head
+    fn food_below_head() -> GameState {
+        let mut game_state = GameState::new(40, 30, 0);
+        clear_food(&mut game_state);
+        game_state.tiles[(10, 5)] = Tile::Food;
+        game_state
+    }
+
+    #[test]
+    fn the_suggested_direction_has_the_best_score() {
+        let game_state = food_below_head();
+        let scores = game_state.suggest_direction_detailed();
+        assert_eq!(scores.get(&Direction::Down), Some(&-6.0));
+        assert_eq!(scores.get(&Direction::Right), Some(&-7.5));
+        assert_eq!(scores.get(&Direction::Up), Some(&-8.0));
+        // Reversing is never an option
+        assert_eq!(scores.get(&Direction::Left), None);
+        assert_eq!(game_state.suggest_direction(), Direction::Down);
+    }
+
+    #[test]
+    fn deadly_directions_are_not_scored_or_suggested() {
+        let mut game_state = food_below_head();
+        game_state.tiles[(4, 5)] = Tile::Wall;
+        let scores = game_state.suggest_direction_detailed();
+        assert_eq!(scores.get(&Direction::Down), None);
+        assert_eq!(game_state.suggest_direction(), Direction::Right);
+        game_state.tiles[(3, 6)] = Tile::Wall;
+        game_state.tiles[(2, 5)] = Tile::Wall;
+        assert!(game_state.suggest_direction_detailed().is_empty());
+        assert_eq!(game_state.suggest_direction(), Direction::Right);
+    }
+
+    // Default game in paint mode with the given goal and the food cleared away, on a small board
+    // that is quick to paint
+    fn paint_game(paint_goal: u32) -> GameState {
+        let rules = Rules { paint_goal: paint_goal, ..Rules::default() };
+        let mut game_state =
+            GameStateBuilder::new().level_size(10, 7).rules(rules).build().unwrap();
+        clear_food(&mut game_state);
+        game_state
+    }
+
+    #[test]
+    fn coverage_counts_the_tiles_the_head_visited() {
+        let mut game_state = paint_game(100);
+        // Only the tile the head starts on is painted, out of the 8x5 tiles inside the walls
+        assert_eq!(game_state.coverage_percent(), 2);
+        for _ in 0..3 {
+            game_state.update(None).unwrap();
+        }
+        assert_eq!(game_state.coverage_percent(), 10);
+        assert!(!game_state.won());
+    }
+
+    #[test]
+    fn painting_enough_of_the_level_wins() {
+        let mut game_state = paint_game(10);
+        game_state.update(None).unwrap();
+        game_state.update(None).unwrap();
+        assert!(!game_state.won());
+        let events = game_state.update(None).unwrap();
+        assert!(events.contains(&GameEvent::Won));
+        assert!(game_state.won());
+    }
+
+    #[test]
+    fn paint_mode_has_its_own_level_id() {
+        assert_eq!(paint_game(0).level_id(), "default");
+        assert_eq!(paint_game(60).level_id(), "default, paint 60%");
+    }
+
+    // Default game in food chain mode with chains of three, without the chain it started with
+    fn chain_game() -> GameState {
+        let rules = Rules { food_chain: 3, ..Rules::default() };
+        let mut game_state = GameStateBuilder::new().rules(rules).build().unwrap();
+        clear_food(&mut game_state);
+        game_state
+    }
+
+    #[test]
+    fn eating_a_chain_in_order_spawns_a_new_chain() {
+        let mut game_state = chain_game();
+        for order in 1..4 {
+            game_state.tiles[(3, 5 + order as usize)] = Tile::ChainFood(order);
+        }
+        for order in 1..3 {
+            game_state.update(None).unwrap();
+            assert_eq!(game_state.expected_food(), order + 1);
+        }
+        let events = game_state.update(None).unwrap();
+        assert!(game_state.snake_alive());
+        assert_eq!(game_state.score(), 3 * game_state.rules().food_value);
+        // The whole chain has been eaten, so a new one starts over from the first food
+        assert_eq!(game_state.expected_food(), 1);
+        let spawned = events.iter()
+            .filter(|event| matches!(**event, GameEvent::SpawnedFood { .. }))
+            .count();
+        assert_eq!(spawned, 3);
+    }
+
+    #[test]
+    fn a_chain_that_only_partly_fit_is_complete_once_all_of_it_is_eaten() {
+        let mut game_state = chain_game();
+        for tile in game_state.tiles.iter_mut() {
+            if *tile == Tile::Floor {
+                *tile = Tile::Wall;
+            }
+        }
+        game_state.tiles[(3, 6)] = Tile::Floor;
+        assert_eq!(game_state.spawn_food_chain(), [(3, 6)]);
+        game_state.tiles[(3, 7)] = Tile::Floor;
+        let events = game_state.update(None).unwrap();
+        assert!(game_state.snake_alive());
+        assert!(events.contains(&GameEvent::SpawnedFood { index: (3, 7) }));
+        assert_eq!(game_state.tiles[(3, 7)], Tile::ChainFood(1));
+        assert_eq!(game_state.expected_food(), 1);
+    }
+
+    #[test]
+    fn eating_a_chain_out_of_order_is_deadly() {
+        let mut game_state = chain_game();
+        game_state.tiles[(3, 6)] = Tile::ChainFood(2);
+        game_state.tiles[(3, 10)] = Tile::ChainFood(1);
+        let events = game_state.update(None).unwrap();
+        assert!(events.contains(&GameEvent::Died { index: (3, 5) }));
+        assert!(!game_state.snake_alive());
+    }
+
+    #[test]
+    fn food_chain_mode_has_its_own_level_id() {
+        assert_eq!(chain_game().level_id(), "default, chain 3");
     }
 }
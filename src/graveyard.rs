@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use preferences::AppInfo;
+
+use game::TileIndex;
+use storage;
+
+// Where past snakes died on every level, as identified by GameState::level_id, so that the spots
+// the player keeps dying at can be marked
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Graveyard {
+    deaths: HashMap<String, Vec<TileIndex>>,
+}
+
+impl Graveyard {
+    pub fn load(app: &AppInfo) -> Self {
+        storage::load(app, "graveyard").unwrap_or_default()
+    }
+
+    // Tiles the head of the snake was on when it died on the given level, oldest first
+    pub fn deaths_on(&self, level_id: &str) -> &[TileIndex] {
+        self.deaths.get(level_id).map_or(&[], |deaths| deaths.as_slice())
+    }
+
+    // Record a death on the given level, forgetting the oldest deaths on it beyond the given
+    // number
+    pub fn record_death(&mut self, level_id: &str, index: TileIndex, capacity: usize) {
+        let deaths = self.deaths.entry(level_id.to_string()).or_default();
+        deaths.push(index);
+        let excess = deaths.len().saturating_sub(capacity);
+        deaths.drain(..excess);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deaths_are_recorded_per_level_oldest_first() {
+        let mut graveyard = Graveyard::default();
+        graveyard.record_death("default", (3, 39), 5);
+        graveyard.record_death("seed 7", (10, 10), 5);
+        graveyard.record_death("default", (29, 5), 5);
+        assert_eq!(graveyard.deaths_on("default"), &[(3, 39), (29, 5)]);
+        assert_eq!(graveyard.deaths_on("seed 7"), &[(10, 10)]);
+        assert!(graveyard.deaths_on("seed 8").is_empty());
+    }
+
+    #[test]
+    fn only_the_most_recent_deaths_are_kept() {
+        let mut graveyard = Graveyard::default();
+        for x in 0..5 {
+            graveyard.record_death("default", (1, x), 3);
+        }
+        assert_eq!(graveyard.deaths_on("default"), &[(1, 2), (1, 3), (1, 4)]);
+        graveyard.record_death("default", (2, 0), 0);
+        assert!(graveyard.deaths_on("default").is_empty());
+    }
+}
@@ -0,0 +1,58 @@
+use std::collections::HashMap;
+use preferences::AppInfo;
+
+use storage;
+
+// Level identifier of the plain level without any generated layout
+pub const DEFAULT_LEVEL_ID: &str = "default";
+
+// Highscores kept separately for every level, as identified by GameState::level_id
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Highscores {
+    scores: HashMap<String, u32>,
+}
+
+impl Highscores {
+    // Load the saved highscores, carrying over the single highscore saved by versions that didn't
+    // keep one per level as the highscore of the default level
+    pub fn load(app: &AppInfo) -> Self {
+        storage::load(app, "highscores").unwrap_or_else(|| {
+            let mut highscores = Highscores::default();
+            if let Some(highscore) = storage::load(app, "highscore") {
+                highscores.set_highscore_for(DEFAULT_LEVEL_ID, highscore);
+            }
+            highscores
+        })
+    }
+
+    pub fn highscore_for(&self, level_id: &str) -> u32 {
+        self.scores.get(level_id).cloned().unwrap_or(0)
+    }
+
+    pub fn set_highscore_for(&mut self, level_id: &str, highscore: u32) {
+        self.scores.insert(level_id.to_string(), highscore);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{GameState, Rules};
+    use super::*;
+
+    #[test]
+    fn each_level_keeps_its_own_highscore() {
+        let default_level = GameState::new(40, 30, 0);
+        let rules = Rules { obstacles: 20, ..Rules::default() };
+        let seeded_level = GameState::with_rules(40, 30, 0, rules);
+        let seed = seeded_level.level_seed();
+        assert_eq!(default_level.level_id(), DEFAULT_LEVEL_ID);
+        assert_eq!(seeded_level.level_id(), format!("seed {}", seed));
+
+        let mut highscores = Highscores::default();
+        highscores.set_highscore_for(&default_level.level_id(), 30);
+        highscores.set_highscore_for(&seeded_level.level_id(), 12);
+        assert_eq!(highscores.highscore_for(DEFAULT_LEVEL_ID), 30);
+        assert_eq!(highscores.highscore_for(&format!("seed {}", seed)), 12);
+        assert_eq!(highscores.highscore_for(&format!("seed {}", seed.wrapping_add(1))), 0);
+    }
+}
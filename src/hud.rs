@@ -0,0 +1,141 @@
+use sdl2::render::Renderer;
+
+use game::GameState;
+use settings::Settings;
+use text;
+
+// Distance in pixels between the HUD and the edges of the board
+const MARGIN: u32 = 2;
+
+// Corner of the board the HUD is drawn in
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum Anchor {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum HudField {
+    Score,
+    Highscore,
+    Length,
+    // Ticks left in time attack; not shown without a time limit
+    Time,
+    // Percentage of the level painted in paint mode; not shown without painting
+    Coverage,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct HudSettings {
+    pub visible: bool,
+    pub anchor: Anchor,
+    // Fields to show, one per line, from top to bottom
+    pub fields: Vec<HudField>,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        HudSettings {
+            visible: false,
+            anchor: Anchor::TopLeft,
+            fields: vec![HudField::Score,
+                         HudField::Highscore,
+                         HudField::Length,
+                         HudField::Time,
+                         HudField::Coverage],
+        }
+    }
+}
+
+// Position of the top left corner of a block of the given size placed in a corner of a board of
+// the given size
+pub fn anchor_position(anchor: Anchor,
+                       (board_width, board_height): (u32, u32),
+                       (width, height): (u32, u32))
+                       -> (i32, i32) {
+    let left = MARGIN as i32;
+    let top = MARGIN as i32;
+    let right = board_width as i32 - MARGIN as i32 - width as i32;
+    let bottom = board_height as i32 - MARGIN as i32 - height as i32;
+    match anchor {
+        Anchor::TopLeft => (left, top),
+        Anchor::TopRight => (right, top),
+        Anchor::BottomLeft => (left, bottom),
+        Anchor::BottomRight => (right, bottom),
+    }
+}
+
+// Text of a HUD line, or None if the field doesn't apply to the game
+fn field_text(field: HudField, game_state: &GameState) -> Option<String> {
+    match field {
+        HudField::Score => Some(format!("SCORE {}", game_state.score())),
+        HudField::Highscore => Some(format!("HIGH {}", game_state.highscore())),
+        HudField::Length => Some(format!("LEN {}", game_state.snake_length())),
+        HudField::Time => game_state.ticks_left().map(|ticks| format!("TIME {}", ticks)),
+        HudField::Coverage => {
+            let goal = game_state.rules().paint_goal;
+            Some(format!("PAINT {}/{}%", game_state.coverage_percent(), goal)).filter(|_| goal > 0)
+        }
+    }
+}
+
+// Draw the HUD onto a board of the given size
+pub fn draw(renderer: &mut Renderer,
+            settings: &Settings,
+            game_state: &GameState,
+            board_size: (u32, u32))
+            -> Result<(), String> {
+    let (theme, settings) = (&settings.theme, &settings.hud);
+    if !settings.visible {
+        return Ok(());
+    }
+    let lines: Vec<String> =
+        settings.fields.iter().filter_map(|&field| field_text(field, game_state)).collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    let line_height = text::text_size("", 1).1 + 1;
+    let width = lines.iter().map(|line| text::text_size(line, 1).0).max().unwrap_or(0);
+    let height = lines.len() as u32 * line_height - 1;
+    let (x, y) = anchor_position(settings.anchor, board_size, (width, height));
+    renderer.set_draw_color(theme.text.color());
+    for (i, line) in lines.iter().enumerate() {
+        // Right-aligned anchors align each line to the right edge
+        let line_x = match settings.anchor {
+            Anchor::TopRight | Anchor::BottomRight => {
+                x + (width - text::text_size(line, 1).0) as i32
+            }
+            _ => x,
+        };
+        text::draw_text(renderer, line, line_x, y + (i as u32 * line_height) as i32, 1)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn anchor_position_places_a_block_in_each_corner() {
+        let board = (320, 240);
+        let block = (40, 10);
+        assert_eq!(anchor_position(Anchor::TopLeft, board, block), (2, 2));
+        assert_eq!(anchor_position(Anchor::TopRight, board, block), (278, 2));
+        assert_eq!(anchor_position(Anchor::BottomLeft, board, block), (2, 228));
+        assert_eq!(anchor_position(Anchor::BottomRight, board, block), (278, 228));
+    }
+
+    #[test]
+    fn anchor_position_follows_the_board_size() {
+        let block = (40, 10);
+        for &(width, height) in &[(100, 80), (640, 480), (1280, 200)] {
+            let (x, y) = anchor_position(Anchor::BottomRight, (width, height), block);
+            assert_eq!((x + 40 + 2, y + 10 + 2), (width as i32, height as i32));
+            assert_eq!(anchor_position(Anchor::TopLeft, (width, height), block), (2, 2));
+        }
+    }
+}
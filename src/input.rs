@@ -0,0 +1,283 @@
+use std::collections::VecDeque;
+
+use game::Direction;
+
+// What to do with an input arriving while the input queue is full
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum OverflowPolicy {
+    // Make room by dropping the input that was queued first
+    DropOldest,
+    // Replace the input that was queued last
+    DropNewest,
+    // Discard the arriving input
+    Ignore,
+}
+
+// Bound on the number of queued inputs, so that inputs pressed faster than the snake moves don't
+// keep it turning long after the player stopped
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
+pub struct QueueLimit {
+    pub capacity: usize,
+    pub overflow: OverflowPolicy,
+}
+
+impl Default for QueueLimit {
+    fn default() -> Self {
+        QueueLimit {
+            capacity: 2,
+            overflow: OverflowPolicy::DropOldest,
+        }
+    }
+}
+
+// Queue an input, applying the overflow policy if the queue is full.  There is always room for
+// at least one input.
+pub fn push_bounded(queue: &mut VecDeque<Direction>, dir: Direction, limit: QueueLimit) {
+    if queue.len() >= limit.capacity.max(1) {
+        match limit.overflow {
+            OverflowPolicy::DropOldest => {
+                queue.pop_front();
+            }
+            OverflowPolicy::DropNewest => {
+                queue.pop_back();
+            }
+            OverflowPolicy::Ignore => return,
+        }
+    }
+    queue.push_back(dir);
+}
+
+// Queue an input as the next turn in a chain of turns, validating it against the turn queued
+// before it (or the snake's current direction if nothing is queued) rather than against the
+// direction the snake happens to be heading in when the input is resolved.  Inputs that repeat or
+// reverse the previous turn in the chain are dropped straight away, so every queued turn can be
+// applied on consecutive ticks: a snake heading left with Up and Right queued turns up and then
+// right, while Up, Down, Right turns up and then right, dropping the Down.
+pub fn queue_turn(queue: &mut VecDeque<Direction>,
+                  dir: Direction,
+                  current_dir: Direction,
+                  limit: QueueLimit) {
+    let prev_dir = queue.back().cloned().unwrap_or(current_dir);
+    if dir == prev_dir || dir == prev_dir.reverse() {
+        return;
+    }
+    let drops_oldest = queue.len() >= limit.capacity.max(1) &&
+                       limit.overflow == OverflowPolicy::DropOldest;
+    push_bounded(queue, dir, limit);
+    // Dropping the oldest turn to make room leaves a new first turn, which was only validated
+    // against the dropped one, so it has to be checked against the current direction instead
+    if drops_oldest {
+        while queue.front().is_some_and(|&front| {
+            front == current_dir || front == current_dir.reverse()
+        }) {
+            queue.pop_front();
+        }
+    }
+}
+
+// Queue an input for a snake that hasn't started moving yet.  Any input but reversing gets the
+// snake going, including one in the direction it already faces, which a chain of turns would
+// drop as a repeat, so the first input that would start the snake is always queued.
+pub fn queue_start(queue: &mut VecDeque<Direction>,
+                   dir: Direction,
+                   current_dir: Direction,
+                   limit: QueueLimit) {
+    if queue.is_empty() && dir != current_dir.reverse() {
+        push_bounded(queue, dir, limit);
+    } else {
+        queue_turn(queue, dir, current_dir, limit);
+    }
+}
+
+// Decide which queued input applies on the current tick, given the direction the snake is
+// currently heading in.  Inputs are taken from the front of the queue in the order they were
+// pressed.  Inputs that don't turn the snake are discarded: repeating the current direction
+// changes nothing, and reversing it would crash the snake into itself.  The first input that
+// does turn the snake is applied, and everything queued after it is returned untouched, to be
+// resolved on later ticks (at most one turn applies per tick).
+pub fn resolve_input(mut queue: VecDeque<Direction>,
+                     current_dir: Direction)
+                     -> (Option<Direction>, VecDeque<Direction>) {
+    while let Some(dir) = queue.pop_front() {
+        if dir != current_dir && dir != current_dir.reverse() {
+            return (Some(dir), queue);
+        }
+    }
+    (None, queue)
+}
+
+// Decide which queued input gets a snake that hasn't started moving yet going.  Unlike when
+// resolving turns, an input in the direction the snake faces counts, and only reversals are
+// discarded.
+pub fn resolve_start(mut queue: VecDeque<Direction>,
+                     current_dir: Direction)
+                     -> (Option<Direction>, VecDeque<Direction>) {
+    while let Some(dir) = queue.pop_front() {
+        if dir != current_dir.reverse() {
+            return (Some(dir), queue);
+        }
+    }
+    (None, queue)
+}
+
+// Direction of a swipe covering the given distance, or None if it is too short to be anything but
+// a tap.  Distances are in normalized touch coordinates, with y growing downwards, and the swipe
+// goes in the direction of whichever axis it moved along the most.
+pub fn swipe_direction((dx, dy): (f32, f32), min_distance: f32) -> Option<Direction> {
+    if (dx * dx + dy * dy).sqrt() < min_distance.max(f32::EPSILON) {
+        None
+    } else if dx.abs() >= dy.abs() {
+        Some(if dx > 0.0 { Direction::Right } else { Direction::Left })
+    } else {
+        Some(if dy > 0.0 { Direction::Down } else { Direction::Up })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use game::Direction::{Down, Left, Right, Up};
+    use super::*;
+
+    #[test]
+    fn resolve_input_applies_the_first_turn_and_keeps_the_rest() {
+        let queue: VecDeque<Direction> = vec![Up, Left, Down].into_iter().collect();
+        let (input, remaining) = resolve_input(queue, Right);
+        assert_eq!(input, Some(Up));
+        assert_eq!(remaining, vec![Left, Down]);
+    }
+
+    #[test]
+    fn resolve_input_discards_repeats_and_reversals_before_a_turn() {
+        let queue: VecDeque<Direction> = vec![Right, Left, Down, Up].into_iter().collect();
+        let (input, remaining) = resolve_input(queue, Right);
+        assert_eq!(input, Some(Down));
+        assert_eq!(remaining, vec![Up]);
+    }
+
+    #[test]
+    fn resolve_input_without_a_turn_applies_nothing() {
+        let queue: VecDeque<Direction> = vec![Right, Left].into_iter().collect();
+        let (input, remaining) = resolve_input(queue, Right);
+        assert_eq!(input, None);
+        assert!(remaining.is_empty());
+        assert_eq!(resolve_input(VecDeque::new(), Right), (None, VecDeque::new()));
+    }
+
+    #[test]
+    fn resolve_start_accepts_the_direction_the_snake_faces() {
+        let queue: VecDeque<Direction> = vec![Left, Right, Up].into_iter().collect();
+        let (input, remaining) = resolve_start(queue, Right);
+        assert_eq!(input, Some(Right));
+        assert_eq!(remaining, vec![Up]);
+    }
+
+    #[test]
+    fn queue_start_queues_the_direction_the_snake_faces() {
+        let mut queue = VecDeque::new();
+        queue_start(&mut queue, Left, Right, QueueLimit::default());
+        assert!(queue.is_empty());
+        queue_start(&mut queue, Right, Right, QueueLimit::default());
+        queue_start(&mut queue, Right, Right, QueueLimit::default());
+        queue_start(&mut queue, Up, Right, QueueLimit::default());
+        assert_eq!(queue, vec![Right, Up]);
+    }
+
+    #[test]
+    fn queue_turn_keeps_a_valid_chain_of_turns() {
+        let mut queue = VecDeque::new();
+        for &dir in &[Up, Right] {
+            queue_turn(&mut queue, dir, Left, QueueLimit::default());
+        }
+        assert_eq!(queue, vec![Up, Right]);
+        // Each turn in the chain applies on consecutive ticks
+        let (input, queue) = resolve_input(queue, Left);
+        assert_eq!(input, Some(Up));
+        let (input, queue) = resolve_input(queue, Up);
+        assert_eq!(input, Some(Right));
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn queue_turn_drops_a_reversal_in_the_middle_of_a_chain() {
+        let mut queue = VecDeque::new();
+        let limit = QueueLimit { capacity: 3, ..QueueLimit::default() };
+        for &dir in &[Up, Down, Right] {
+            queue_turn(&mut queue, dir, Left, limit);
+        }
+        assert_eq!(queue, vec![Up, Right]);
+    }
+
+    #[test]
+    fn queue_turn_drops_repeats_of_the_previous_turn() {
+        let mut queue = VecDeque::new();
+        for &dir in &[Left, Up, Up] {
+            queue_turn(&mut queue, dir, Left, QueueLimit::default());
+        }
+        assert_eq!(queue, vec![Up]);
+    }
+
+    #[test]
+    fn drop_oldest_makes_room_by_dropping_the_first_input() {
+        let limit = QueueLimit { capacity: 2, overflow: OverflowPolicy::DropOldest };
+        let mut queue = VecDeque::new();
+        for &dir in &[Up, Left, Down] {
+            push_bounded(&mut queue, dir, limit);
+        }
+        assert_eq!(queue, vec![Left, Down]);
+    }
+
+    #[test]
+    fn drop_newest_replaces_the_last_input() {
+        let limit = QueueLimit { capacity: 2, overflow: OverflowPolicy::DropNewest };
+        let mut queue = VecDeque::new();
+        for &dir in &[Up, Left, Down] {
+            push_bounded(&mut queue, dir, limit);
+        }
+        assert_eq!(queue, vec![Up, Down]);
+    }
+
+    #[test]
+    fn ignore_discards_inputs_arriving_at_a_full_queue() {
+        let limit = QueueLimit { capacity: 2, overflow: OverflowPolicy::Ignore };
+        let mut queue = VecDeque::new();
+        for &dir in &[Up, Left, Down] {
+            push_bounded(&mut queue, dir, limit);
+        }
+        assert_eq!(queue, vec![Up, Left]);
+    }
+
+    #[test]
+    fn a_full_queue_still_has_room_for_one_input() {
+        let limit = QueueLimit { capacity: 0, overflow: OverflowPolicy::Ignore };
+        let mut queue = VecDeque::new();
+        push_bounded(&mut queue, Up, limit);
+        assert_eq!(queue, vec![Up]);
+    }
+
+    #[test]
+    fn dropping_the_oldest_turn_of_a_chain_revalidates_the_new_first_turn() {
+        let limit = QueueLimit { capacity: 2, overflow: OverflowPolicy::DropOldest };
+        let mut queue = VecDeque::new();
+        // Heading left, Up then Right is a valid chain, but Right alone would reverse the snake
+        for &dir in &[Up, Right, Up] {
+            queue_turn(&mut queue, dir, Left, limit);
+        }
+        assert_eq!(queue, vec![Up]);
+    }
+
+    #[test]
+    fn swipes_go_along_the_axis_they_moved_along_the_most() {
+        assert_eq!(swipe_direction((0.2, 0.05), 0.05), Some(Right));
+        assert_eq!(swipe_direction((-0.2, 0.1), 0.05), Some(Left));
+        assert_eq!(swipe_direction((0.05, 0.2), 0.05), Some(Down));
+        assert_eq!(swipe_direction((-0.1, -0.2), 0.05), Some(Up));
+    }
+
+    #[test]
+    fn short_swipes_are_taps() {
+        assert_eq!(swipe_direction((0.02, 0.03), 0.05), None);
+        assert_eq!(swipe_direction((0.0, 0.0), 0.0), None);
+    }
+}
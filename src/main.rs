@@ -1,22 +1,143 @@
+extern crate app_dirs;
+extern crate libc;
 extern crate ndarray;
 extern crate preferences;
 extern crate rand;
 extern crate sdl2;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 
+use std::str::FromStr;
 use preferences::AppInfo;
 
+use game::{OvertimeCondition, StartMode};
+
+pub mod chaos;
+pub mod controller;
 pub mod engine;
 pub mod game;
+pub mod graveyard;
+pub mod highscores;
+pub mod hud;
+pub mod input;
+pub mod rate;
+pub mod replay;
+pub mod settings;
+pub mod signal;
+pub mod spawn;
+pub mod storage;
+pub mod summary;
+pub mod text;
+pub mod theme;
+pub mod trace;
 
 const APP_INFO: AppInfo = AppInfo {
     name: "snake",
     author: "onasauri",
 };
 
+// Parse the value following a command line flag
+fn flag_value<T, I>(args: &mut I, flag: &str) -> Result<T, String>
+    where T: FromStr,
+          I: Iterator<Item = String>
+{
+    let value = args.next().ok_or(format!("{} requires a value", flag))?;
+    value.parse().map_err(|_| format!("Invalid value for {}: {}", flag, value))
+}
+
+fn parse_args() -> Result<engine::Options, String> {
+    let mut options = engine::Options::default();
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--boards" => options.boards = flag_value(&mut args, &arg)?,
+            "--input" => options.input_device = Some(flag_value(&mut args, &arg)?),
+            "--list-controllers" => options.list_controllers = true,
+            "--debug" => options.debug = true,
+            "--scale" => options.render_scale = flag_value(&mut args, &arg)?,
+            "--far-food" => options.far_food = true,
+            "--no-save" => options.no_save = true,
+            "--wait-for-input" => options.start_mode = Some(StartMode::WaitForInput),
+            "--trace" => options.trace = Some(flag_value(&mut args, &arg)?),
+            "--food-decay" => options.food_decay = Some(flag_value(&mut args, &arg)?),
+            "--food-floor" => options.food_value_floor = Some(flag_value(&mut args, &arg)?),
+            "--win-fill" => options.win_fill_percent = Some(flag_value(&mut args, &arg)?),
+            "--paint" => options.paint_goal = Some(flag_value(&mut args, &arg)?),
+            "--ink-zones" => options.ink_zones = Some(flag_value(&mut args, &arg)?),
+            "--food-chain" => options.food_chain = Some(flag_value(&mut args, &arg)?),
+            "--wall-breakers" => options.wall_breaker_chance = Some(flag_value(&mut args, &arg)?),
+            "--big-food" => options.big_food_chance = Some(flag_value(&mut args, &arg)?),
+            "--obstacles" => options.obstacles = Some(flag_value(&mut args, &arg)?),
+            "--keep-pockets" => options.fill_pockets = Some(false),
+            "--random-obstacles" => options.new_layout_on_reset = Some(true),
+            "--ghost-walls" => options.ghost_walls = Some(flag_value(&mut args, &arg)?),
+            "--ghost-wall-period" => {
+                options.ghost_wall_period = Some(flag_value(&mut args, &arg)?);
+            }
+            "--max-length" => options.max_length = Some(flag_value(&mut args, &arg)?),
+            "--tail-biting" => options.tail_biting = Some(true),
+            "--time-limit" => options.time_limit = Some(flag_value(&mut args, &arg)?),
+            "--overtime" => options.overtime = Some(flag_value(&mut args, &arg)?),
+            "--overtime-near-win" => {
+                let segments = flag_value(&mut args, &arg)?;
+                options.overtime_condition = Some(OvertimeCondition::NearWin {
+                                                      segments: segments,
+                                                  });
+            }
+            "--overtime-after-eating" => {
+                let ticks = flag_value(&mut args, &arg)?;
+                options.overtime_condition = Some(OvertimeCondition::RecentlyAte { ticks: ticks });
+            }
+            "--chaos-controls" => options.chaos_interval = Some(flag_value(&mut args, &arg)?),
+            "--chaos-transforms" => {
+                let transforms: String = flag_value(&mut args, &arg)?;
+                let transforms = transforms.split(',').map(str::parse).collect::<Result<_, _>>()?;
+                options.chaos_transforms = Some(transforms);
+            }
+            "--food-protection" => {
+                options.food_protection_radius = Some(flag_value(&mut args, &arg)?)
+            }
+            "--food-protection-ahead" => {
+                options.food_protection_ahead = Some(flag_value(&mut args, &arg)?)
+            }
+            _ => return Err(format!("Unknown argument: {}", arg)),
+        }
+    }
+    Ok(options)
+}
+
+// Print the names of the connected controllers, which can be passed to --input
+fn list_controllers() -> Result<(), String> {
+    let sdl = sdl2::init()?;
+    let names = controller::connected_names(&sdl.game_controller()?)?;
+    if names.is_empty() {
+        println!("No controllers connected");
+    }
+    for name in names {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
 fn main() {
-    match engine::init() {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(s) => {
+            println!("{}", s);
+            return;
+        }
+    };
+    if options.list_controllers {
+        if let Err(s) = list_controllers() {
+            println!("Failed to list controllers: {}", s);
+        }
+        return;
+    }
+    if let Err(s) = signal::install_handlers() {
+        println!("{}", s);
+    }
+    match engine::init(options) {
         Ok(mut engine) => {
             if let Err(s) = engine.run() {
                 println!("Runtime error: {}", s)
@@ -0,0 +1,76 @@
+use std::time::{Duration, Instant};
+
+// Length of the intervals rates are averaged over
+const RATE_INTERVAL: Duration = Duration::from_millis(500);
+
+// Number of events per second, given how many happened over how long
+pub fn events_per_second(count: u32, elapsed: Duration) -> f32 {
+    let seconds = elapsed.as_secs_f32();
+    if seconds > 0.0 { count as f32 / seconds } else { 0.0 }
+}
+
+// Measures how often something happens, such as frames being rendered, in events per second
+// averaged over the last complete interval
+pub struct RateCounter {
+    count: u32,
+    since: Instant,
+    rate: Option<f32>,
+}
+
+impl RateCounter {
+    pub fn new() -> Self {
+        RateCounter {
+            count: 0,
+            since: Instant::now(),
+            rate: None,
+        }
+    }
+
+    // Count an event, returning whether that completed an interval and updated the rate
+    pub fn count(&mut self) -> bool {
+        self.count += 1;
+        let elapsed = self.since.elapsed();
+        if elapsed < RATE_INTERVAL {
+            return false;
+        }
+        self.rate = Some(events_per_second(self.count, elapsed));
+        self.count = 0;
+        self.since = Instant::now();
+        true
+    }
+
+    // The measured rate, once the first interval has completed
+    pub fn rate(&self) -> Option<f32> {
+        self.rate
+    }
+}
+
+impl Default for RateCounter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_per_second_divides_the_count_by_the_time_taken() {
+        assert_eq!(events_per_second(30, Duration::from_millis(500)), 60.0);
+        assert_eq!(events_per_second(15, Duration::from_secs(3)), 5.0);
+        assert_eq!(events_per_second(0, Duration::from_secs(1)), 0.0);
+    }
+
+    #[test]
+    fn events_per_second_is_zero_when_no_time_passed() {
+        assert_eq!(events_per_second(10, Duration::from_secs(0)), 0.0);
+    }
+
+    #[test]
+    fn rate_counter_has_no_rate_before_an_interval_completes() {
+        let mut counter = RateCounter::new();
+        assert!(!counter.count());
+        assert_eq!(counter.rate(), None);
+    }
+}
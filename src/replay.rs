@@ -0,0 +1,173 @@
+use game::{Direction, GameState};
+
+// Number of ticks between the snapshots a ReplayPlayer keeps for seeking
+const SNAPSHOT_INTERVAL: usize = 50;
+
+// A recorded game: the state it started from and the input given to GameState::update on each
+// tick.  Since each game state carries its own random number generator, feeding the same inputs
+// to a copy of the initial state reproduces the game exactly.
+#[derive(Clone)]
+pub struct Replay {
+    initial: GameState,
+    inputs: Vec<Option<Direction>>,
+}
+
+impl Replay {
+    pub fn new(initial: &GameState) -> Self {
+        Replay {
+            initial: initial.clone(),
+            inputs: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, input: Option<Direction>) {
+        self.inputs.push(input);
+    }
+
+    // Number of recorded ticks
+    pub fn len(&self) -> usize {
+        self.inputs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inputs.is_empty()
+    }
+}
+
+// Plays back a replay one tick at a time, with support for pausing and seeking.  GameState::update
+// only moves forward, so seeking backwards restarts from the closest earlier snapshot; snapshots
+// are taken every SNAPSHOT_INTERVAL ticks as the replay is first played through.
+pub struct ReplayPlayer {
+    replay: Replay,
+    // snapshots[i] is the game state after i * SNAPSHOT_INTERVAL ticks
+    snapshots: Vec<GameState>,
+    game_state: GameState,
+    tick: usize,
+    paused: bool,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        let game_state = replay.initial.clone();
+        ReplayPlayer {
+            snapshots: vec![game_state.clone()],
+            game_state: game_state,
+            replay: replay,
+            tick: 0,
+            paused: false,
+        }
+    }
+
+    pub fn game_state(&self) -> &GameState {
+        &self.game_state
+    }
+
+    // Number of ticks played back so far
+    pub fn tick(&self) -> usize {
+        self.tick
+    }
+
+    pub fn len(&self) -> usize {
+        self.replay.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replay.is_empty()
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.tick == self.replay.len()
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn step_forward(&mut self) -> Result<(), String> {
+        if self.at_end() {
+            return Ok(());
+        }
+        self.game_state.update(self.replay.inputs[self.tick])?;
+        self.tick += 1;
+        // Snapshots are only missing for ticks beyond the furthest point played so far
+        if self.tick == self.snapshots.len() * SNAPSHOT_INTERVAL {
+            self.snapshots.push(self.game_state.clone());
+        }
+        Ok(())
+    }
+
+    pub fn step_back(&mut self) -> Result<(), String> {
+        if self.tick > 0 {
+            let tick = self.tick - 1;
+            self.seek(tick)?;
+        }
+        Ok(())
+    }
+
+    // Jump to the state after the given number of ticks, clamped to the length of the replay
+    pub fn seek(&mut self, tick: usize) -> Result<(), String> {
+        let tick = ::std::cmp::min(tick, self.replay.len());
+        if tick < self.tick {
+            let snapshot = ::std::cmp::min(tick / SNAPSHOT_INTERVAL, self.snapshots.len() - 1);
+            self.game_state = self.snapshots[snapshot].clone();
+            self.tick = snapshot * SNAPSHOT_INTERVAL;
+        }
+        while self.tick < tick {
+            self.step_forward()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::{Direction, GameState};
+    use super::*;
+
+    // Whether two game states are in the same position, as far as a replay can tell
+    fn same_position(a: &GameState, b: &GameState) -> bool {
+        a.score() == b.score() && a.tiles() == b.tiles() && a.snake_dir() == b.snake_dir()
+    }
+
+    #[test]
+    fn seeking_matches_stepping_from_the_start() {
+        let mut game_state = GameState::new(40, 30, 0);
+        game_state.toggle_walls();
+        let mut replay = Replay::new(&game_state);
+        let turns = [Some(Direction::Down), Some(Direction::Right)];
+        for i in 0..130 {
+            let input = if i % 7 == 6 { turns[i / 7 % 2] } else { None };
+            game_state.update(input).unwrap();
+            replay.record(input);
+        }
+
+        let mut seeked = ReplayPlayer::new(replay.clone());
+        seeked.seek(130).unwrap();
+        assert!(same_position(seeked.game_state(), &game_state));
+        for &tick in &[120, 75, 50, 3, 0] {
+            let mut stepped = ReplayPlayer::new(replay.clone());
+            for _ in 0..tick {
+                stepped.step_forward().unwrap();
+            }
+            seeked.seek(tick).unwrap();
+            assert_eq!(seeked.tick(), tick);
+            assert!(same_position(seeked.game_state(), stepped.game_state()));
+        }
+    }
+
+    #[test]
+    fn seeking_is_clamped_to_the_replay() {
+        let game_state = GameState::new(40, 30, 0);
+        let mut replay = Replay::new(&game_state);
+        replay.record(None);
+        replay.record(None);
+        let mut player = ReplayPlayer::new(replay);
+        player.seek(10).unwrap();
+        assert_eq!(player.tick(), 2);
+        assert!(player.at_end());
+    }
+}
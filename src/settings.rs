@@ -0,0 +1,63 @@
+use controller::InputDevice;
+use hud::HudSettings;
+use input::QueueLimit;
+use theme::Theme;
+
+// Player preferences that persist across sessions independently of the game state
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Settings {
+    pub hud: HudSettings,
+    // Whether to save the game on exit and resume it on the next start; the highscore is kept
+    // either way
+    pub auto_save: bool,
+    pub theme: Theme,
+    // Whether to show progress towards the length needed to win
+    pub progress_bar: bool,
+    // Whether the snake's head slides between tiles instead of jumping, and whether it also
+    // slides across the level edges it wraps around
+    pub smooth_movement: bool,
+    pub wrap_animation: bool,
+    // Whether the parts of the level edges the snake can wrap around through glow
+    pub edge_glow: bool,
+    // Whether quickly pressed turns are validated as a chain when they are queued, rather than
+    // each against the snake's direction when it is its turn to be applied
+    pub chain_turns: bool,
+    pub input_queue: QueueLimit,
+    // Shortest swipe on a touch screen that steers the snake, as a fraction of the window size
+    pub swipe_distance: f32,
+    pub input_device: InputDevice,
+    // Whether the board shakes briefly when the snake dies or breaks through a wall
+    pub screen_shake: bool,
+    // Whether tiles are also told apart by patterns, for players who can't rely on the colors
+    pub patterns: bool,
+    // Whether to show a summary of the game with a rating once it is over
+    pub summary: bool,
+    // Whether to mark where past snakes died on the current level, and how many of the most
+    // recent deaths on each level to remember
+    pub graveyard: bool,
+    pub graveyard_size: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            hud: HudSettings::default(),
+            auto_save: true,
+            theme: Theme::default(),
+            progress_bar: false,
+            smooth_movement: false,
+            wrap_animation: true,
+            edge_glow: true,
+            chain_turns: true,
+            input_queue: QueueLimit::default(),
+            swipe_distance: 0.05,
+            input_device: InputDevice::AnyController,
+            screen_shake: true,
+            patterns: false,
+            summary: true,
+            graveyard: true,
+            graveyard_size: 20,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use libc;
+
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request_shutdown(_signal: libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+// Turn SIGINT and SIGTERM into a shutdown request, which the main loop polls so it can save
+// and exit cleanly.  This has to happen before SDL is initialized: SDL only installs its own
+// handlers (which post a quit event instead) where no other handler has been installed yet.
+pub fn install_handlers() -> Result<(), String> {
+    for &signal in &[libc::SIGINT, libc::SIGTERM] {
+        let handler = request_shutdown as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        if unsafe { libc::signal(signal, handler) } == libc::SIG_ERR {
+            return Err(format!("Failed to install a handler for signal {}", signal));
+        }
+    }
+    Ok(())
+}
+
+pub fn shutdown_requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use libc;
+    use super::*;
+
+    #[test]
+    fn terminating_requests_a_shutdown() {
+        install_handlers().unwrap();
+        assert!(!shutdown_requested());
+        assert_eq!(unsafe { libc::raise(libc::SIGTERM) }, 0);
+        assert!(shutdown_requested());
+    }
+}
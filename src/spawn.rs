@@ -0,0 +1,101 @@
+use rand::{self, Rng, SeedableRng, XorShiftRng};
+
+use game::{GameState, TileIndex};
+
+// Decides where new food spawns
+pub trait SpawnStrategy {
+    // Pick the tile to spawn food on, or None if there is nowhere to put it
+    fn pick(&mut self, state: &GameState) -> Option<TileIndex>;
+
+    // Copy of the strategy, so that game states holding one can be cloned
+    fn box_clone(&self) -> BoxedSpawnStrategy;
+}
+
+// Boxed strategy, as held by a game state
+pub type BoxedSpawnStrategy = Box<dyn SpawnStrategy>;
+
+impl Clone for BoxedSpawnStrategy {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+// Spawn food on any free tile with equal probability.  The strategy keeps its own random number
+// generator, so that a copy of a game state spawns food in the same places as the original.
+#[derive(Clone)]
+pub struct UniformRandom {
+    rng: XorShiftRng,
+}
+
+impl UniformRandom {
+    pub fn new() -> Self {
+        UniformRandom { rng: rand::thread_rng().gen() }
+    }
+
+    // Strategy that always spawns food in the same places given the same seed and game
+    pub fn with_seed(seed: u32) -> Self {
+        UniformRandom { rng: XorShiftRng::from_seed([seed, 0x2545_f491, 0x9e37_79b9, 1]) }
+    }
+}
+
+impl Default for UniformRandom {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SpawnStrategy for UniformRandom {
+    fn pick(&mut self, state: &GameState) -> Option<TileIndex> {
+        self.rng.choose(&state.food_spawn_tiles()).cloned()
+    }
+
+    fn box_clone(&self) -> BoxedSpawnStrategy {
+        Box::new(self.clone())
+    }
+}
+
+// Spawn food on the free tile farthest from the snake's head, for a long trip every time
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FarthestFromHead;
+
+impl SpawnStrategy for FarthestFromHead {
+    fn pick(&mut self, state: &GameState) -> Option<TileIndex> {
+        let head = state.snake_head();
+        state.food_spawn_tiles().into_iter().max_by_key(|&index| state.distance(index, head))
+    }
+
+    fn box_clone(&self) -> BoxedSpawnStrategy {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game::GameState;
+    use super::*;
+
+    #[test]
+    fn uniform_random_with_a_seed_always_picks_the_same_free_tile() {
+        let game_state = GameState::new(40, 30, 0);
+        let pick = UniformRandom::with_seed(5).pick(&game_state);
+        assert!(pick.is_some());
+        assert_eq!(UniformRandom::with_seed(5).pick(&game_state), pick);
+        assert!(game_state.food_spawn_tiles().contains(&pick.unwrap()));
+        let picks = (0..10).map(|seed| UniformRandom::with_seed(seed).pick(&game_state));
+        assert!(picks.collect::<Vec<_>>().iter().any(|&other| other != pick));
+    }
+
+    #[test]
+    fn farthest_from_head_picks_the_free_tile_farthest_from_the_head() {
+        let game_state = GameState::new(40, 30, 0);
+        let head = game_state.snake_head();
+        let tiles = game_state.food_spawn_tiles();
+        let pick = FarthestFromHead.pick(&game_state).unwrap();
+        assert!(tiles.contains(&pick));
+        assert!(tiles.iter().all(|&index| {
+            game_state.distance(index, head) <= game_state.distance(pick, head)
+        }));
+        // Halfway around the level both ways is 35 tiles away, unless food takes that tile
+        assert!(game_state.distance(pick, head) >= 34);
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use app_dirs::{self, AppDataType, AppInfo};
+use preferences::{Preferences, PreferencesError};
+
+// Directory all data is saved in
+pub fn dir(app: &AppInfo) -> Result<PathBuf, String> {
+    app_dirs::get_app_root(AppDataType::UserConfig, app).map_err(|e| format!("{}", e))
+}
+
+// Path of the file data saved under the given key is stored in.  Files are read and written here
+// rather than by the preferences crate, so that the file backed up is always the one that failed
+// to load; they are still named the way that crate names them, so older saves keep loading.
+fn path_in(dir: &Path, key: &str) -> PathBuf {
+    dir.join(format!("{}.prefs.json", key))
+}
+
+// Move an unreadable file out of the way, returning where it was moved to
+fn back_up(path: &Path) -> Result<PathBuf, String> {
+    let mut backup = path.to_path_buf().into_os_string();
+    backup.push(".bak");
+    let backup = PathBuf::from(backup);
+    fs::rename(path, &backup).map_err(|e| format!("{}", e))?;
+    Ok(backup)
+}
+
+fn load_from_path<T: Preferences>(path: &Path) -> Option<T> {
+    let loaded = File::open(path)
+        .map_err(PreferencesError::Io)
+        .and_then(|mut file| T::load_from(&mut file));
+    match loaded {
+        Ok(data) => Some(data),
+        Err(PreferencesError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => {
+            println!("Warning: failed to load {}: {}", path.display(), e);
+            match back_up(path) {
+                Ok(backup) => println!("The unreadable file was moved to {}", backup.display()),
+                Err(e) => println!("Warning: failed to back up the unreadable file: {}", e),
+            }
+            None
+        }
+    }
+}
+
+fn save_to_path<T: Preferences>(path: &Path, data: &T) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}", e))?;
+    }
+    let mut file = File::create(path).map_err(|e| format!("{}", e))?;
+    data.save_to(&mut file).map_err(|e| format!("{}", e))
+}
+
+// Load data saved under the given key, returning None if nothing has been saved yet.  If the
+// saved data exists but can't be loaded, a warning is printed and the broken file is backed up
+// before returning None, so that it isn't silently overwritten later.
+pub fn load<T: Preferences>(app: &AppInfo, key: &str) -> Option<T> {
+    match dir(app) {
+        Ok(dir) => load_from_path(&path_in(&dir, key)),
+        Err(e) => {
+            println!("Warning: failed to load {}: {}", key, e);
+            None
+        }
+    }
+}
+
+pub fn load_or_default<T: Preferences + Default>(app: &AppInfo, key: &str) -> T {
+    load(app, key).unwrap_or_default()
+}
+
+// Save data under the given key in the given directory, which is normally the one from dir
+pub fn save_in<T: Preferences>(dir: &Path, key: &str, data: &T) -> Result<(), String> {
+    save_to_path(&path_in(dir, key), data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::{load_from_path, save_to_path};
+
+    // A path in the temporary directory that is unique to the test
+    fn temp_path(name: &str) -> PathBuf {
+        let path = ::std::env::temp_dir()
+            .join(format!("snake-storage-{}-{}.json", ::std::process::id(), name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn backup_of(path: &Path) -> PathBuf {
+        let mut backup = path.to_path_buf().into_os_string();
+        backup.push(".bak");
+        PathBuf::from(backup)
+    }
+
+    #[test]
+    fn missing_file_loads_nothing_and_is_not_backed_up() {
+        let path = temp_path("missing");
+        assert_eq!(load_from_path::<u32>(&path), None);
+        assert!(!backup_of(&path).exists());
+    }
+
+    #[test]
+    fn corrupt_file_loads_nothing_and_is_backed_up() {
+        let path = temp_path("corrupt");
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(load_from_path::<u32>(&path), None);
+        assert!(!path.exists());
+        let backup = backup_of(&path);
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "not json");
+        fs::remove_file(backup).unwrap();
+    }
+
+    #[test]
+    fn saved_data_loads_back() {
+        let path = temp_path("saved");
+        save_to_path(&path, &42u32).unwrap();
+        assert_eq!(load_from_path::<u32>(&path), Some(42));
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,135 @@
+use sdl2::rect::Rect;
+use sdl2::render::{BlendMode, Renderer};
+
+use game::GameState;
+use settings::Settings;
+use text;
+
+// Opacity of the layer dimming the board behind the summary
+const DIM_ALPHA: u8 = 176;
+
+// Statistics of a finished game
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GameStats {
+    pub score: u32,
+    pub length: usize,
+    // Number of ticks the snake moved for
+    pub ticks: u32,
+    pub food_eaten: u32,
+    pub won: bool,
+}
+
+impl GameStats {
+    pub fn of(game_state: &GameState) -> Self {
+        GameStats {
+            score: game_state.score(),
+            length: game_state.snake_length(),
+            ticks: game_state.tick(),
+            food_eaten: game_state.food_eaten(),
+            won: game_state.won(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Rating {
+    S,
+    A,
+    B,
+    C,
+}
+
+// Rate a game by its score and by its pace, the food eaten per hundred ticks, so that going
+// straight for the food counts as well as surviving for long.  Winning always rates S.
+pub fn rate(stats: &GameStats) -> Rating {
+    let pace = (stats.food_eaten * 100).checked_div(stats.ticks).unwrap_or(0);
+    if stats.won || (stats.score >= 400 && pace >= 4) {
+        Rating::S
+    } else if stats.score >= 200 || (stats.score >= 100 && pace >= 5) {
+        Rating::A
+    } else if stats.score >= 80 {
+        Rating::B
+    } else {
+        Rating::C
+    }
+}
+
+// Draw the summary of a finished game over its board, which is dimmed to set it apart
+pub fn draw(renderer: &mut Renderer,
+            settings: &Settings,
+            game_state: &GameState,
+            (board_width, board_height): (u32, u32))
+            -> Result<(), String> {
+    let theme = &settings.theme;
+    renderer.set_blend_mode(BlendMode::Blend);
+    renderer.set_draw_color(theme.background.with_alpha(DIM_ALPHA));
+    renderer.fill_rect(Rect::new(0, 0, board_width, board_height))?;
+    renderer.set_blend_mode(BlendMode::None);
+
+    let stats = GameStats::of(game_state);
+    let title = if stats.won {
+        "YOU WIN!"
+    } else if game_state.timed_out() {
+        "TIME UP"
+    } else {
+        "GAME OVER"
+    };
+    let rating = format!("{:?}", rate(&stats));
+    // Lines with the scale they are drawn at
+    let lines = [(title.to_string(), 2),
+                 (format!("SCORE {}", stats.score), 1),
+                 (format!("LENGTH {}", stats.length), 1),
+                 (format!("MOVES {}", stats.ticks), 1),
+                 (format!("FOOD {}", stats.food_eaten), 1),
+                 (format!("RATING {}", rating), 2)];
+    let height: u32 =
+        lines.iter().map(|&(ref line, scale)| text::text_size(line, scale).1 + 2).sum();
+    let mut y = (board_height as i32 - height as i32) / 2;
+    renderer.set_draw_color(theme.text.color());
+    for &(ref line, scale) in &lines {
+        let (width, line_height) = text::text_size(line, scale);
+        text::draw_text(renderer, line, (board_width as i32 - width as i32) / 2, y, scale)?;
+        y += line_height as i32 + 2;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(score: u32, ticks: u32, food_eaten: u32) -> GameStats {
+        GameStats {
+            score: score,
+            length: 3 + food_eaten as usize,
+            ticks: ticks,
+            food_eaten: food_eaten,
+            won: false,
+        }
+    }
+
+    #[test]
+    fn winning_always_rates_s() {
+        let stats = GameStats { won: true, ..stats(0, 0, 0) };
+        assert_eq!(rate(&stats), Rating::S);
+    }
+
+    #[test]
+    fn a_high_score_at_a_fast_pace_rates_s() {
+        assert_eq!(rate(&stats(450, 1000, 45)), Rating::S);
+        // The same score eaten slowly only rates A
+        assert_eq!(rate(&stats(450, 3000, 45)), Rating::A);
+    }
+
+    #[test]
+    fn a_moderate_score_rates_a_at_a_fast_pace_and_b_otherwise() {
+        assert_eq!(rate(&stats(120, 200, 12)), Rating::A);
+        assert_eq!(rate(&stats(120, 1000, 12)), Rating::B);
+    }
+
+    #[test]
+    fn a_low_score_rates_c() {
+        assert_eq!(rate(&stats(40, 100, 4)), Rating::C);
+        assert_eq!(rate(&stats(0, 0, 0)), Rating::C);
+    }
+}
@@ -0,0 +1,77 @@
+use sdl2::pixels::Color;
+
+// An RGB color that can be stored in the settings
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    pub fn color(&self) -> Color {
+        Color::RGB(self.0, self.1, self.2)
+    }
+
+    // The color with the given opacity, for drawing with blending enabled
+    pub fn with_alpha(&self, alpha: u8) -> Color {
+        Color::RGBA(self.0, self.1, self.2, alpha)
+    }
+}
+
+// Colors used for drawing the game
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct Theme {
+    pub background: Rgb,
+    pub floor: Rgb,
+    pub dead_floor: Rgb,
+    pub won_floor: Rgb,
+    pub wall: Rgb,
+    pub food: Rgb,
+    pub wall_breaker: Rgb,
+    pub snake: Rgb,
+    pub snake_head: Rgb,
+    pub uninked_zone: Rgb,
+    pub inked_zone: Rgb,
+    // Floor the snake has painted in paint mode
+    pub painted_floor: Rgb,
+    // Glow along the level edges the snake can wrap around through
+    pub edge_glow: Rgb,
+    pub text: Rgb,
+    // Coordinate labels shown in debug mode
+    pub coordinates: Rgb,
+    // Tints for floor the snake's head can and can't reach, shown in debug mode
+    pub reachable: Rgb,
+    pub unreachable: Rgb,
+    // Scores the autopilot gives each direction, shown in debug mode
+    pub bot_scores: Rgb,
+    // Markers where past snakes died, drawn faintly
+    pub graves: Rgb,
+    pub progress_bar: Rgb,
+    pub progress_bar_background: Rgb,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            background: Rgb(0, 0, 0),
+            floor: Rgb(0, 0, 255),
+            dead_floor: Rgb(128, 0, 0),
+            won_floor: Rgb(0, 128, 0),
+            wall: Rgb(255, 0, 0),
+            food: Rgb(255, 255, 0),
+            wall_breaker: Rgb(255, 128, 0),
+            snake: Rgb(0, 255, 0),
+            snake_head: Rgb(160, 255, 160),
+            uninked_zone: Rgb(0, 160, 255),
+            inked_zone: Rgb(96, 0, 160),
+            painted_floor: Rgb(0, 96, 255),
+            edge_glow: Rgb(0, 255, 255),
+            text: Rgb(255, 255, 255),
+            coordinates: Rgb(96, 96, 160),
+            reachable: Rgb(0, 255, 128),
+            unreachable: Rgb(255, 0, 255),
+            bot_scores: Rgb(255, 255, 255),
+            graves: Rgb(255, 255, 255),
+            progress_bar: Rgb(255, 255, 255),
+            progress_bar_background: Rgb(64, 64, 64),
+        }
+    }
+}
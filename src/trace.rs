@@ -0,0 +1,115 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use serde_json;
+
+use game::{Direction, GameState, TileIndex};
+
+// One line of the trace, describing the game right after an update
+#[derive(Serialize)]
+struct TraceRecord {
+    tick: u32,
+    input: Option<Direction>,
+    direction: Direction,
+    head: TileIndex,
+    score: u32,
+}
+
+// Line of the trace marking that the game was reset, after which ticks count up from 0 again
+#[derive(Serialize)]
+struct ResetRecord {
+    reset: bool,
+    level: String,
+}
+
+// Audit log of every update of a game, written as one JSON object per line.  Unlike a replay,
+// which only stores what is needed to play a game back, this records the resulting state of
+// every tick so that runs can be checked against it.  Updates in which the game didn't move on
+// aren't ticks and shouldn't be recorded, and resets are marked so ticks never go backwards
+// without warning.
+pub struct Trace {
+    writer: BufWriter<File>,
+}
+
+impl Trace {
+    pub fn create(path: &str) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Failed to create {}: {}", path, e))?;
+        Ok(Trace { writer: BufWriter::new(file) })
+    }
+
+    // Record the state of the game after an update with the given input, given the tick it was
+    // on before the update.  Updates that didn't move the game on to its next tick, such as
+    // those after it ended, are skipped.
+    pub fn record(&mut self,
+                  game_state: &GameState,
+                  input: Option<Direction>,
+                  tick_before: u32)
+                  -> Result<(), String> {
+        if game_state.tick() == tick_before {
+            return Ok(());
+        }
+        let record = TraceRecord {
+            tick: game_state.tick(),
+            input: input,
+            direction: game_state.snake_dir(),
+            head: game_state.snake_head(),
+            score: game_state.score(),
+        };
+        serde_json::to_writer(&mut self.writer, &record).map_err(|e| format!("{}", e))?;
+        writeln!(self.writer).map_err(|e| format!("{}", e))
+    }
+
+    // Record that the game was reset to a new one
+    pub fn record_reset(&mut self, game_state: &GameState) -> Result<(), String> {
+        let record = ResetRecord {
+            reset: true,
+            level: game_state.level_id(),
+        };
+        serde_json::to_writer(&mut self.writer, &record).map_err(|e| format!("{}", e))?;
+        writeln!(self.writer).map_err(|e| format!("{}", e))
+    }
+
+    pub fn flush(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|e| format!("{}", e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use game::GameState;
+    use super::*;
+
+    #[test]
+    fn each_tick_and_reset_is_one_line() {
+        let path = ::std::env::temp_dir()
+            .join(format!("snake-trace-{}.jsonl", ::std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut game_state = GameState::new(40, 30, 0);
+        let mut trace = Trace::create(path).unwrap();
+        // Only the first three updates are ticks: the ones after the crash into the wall come
+        // after the game ended
+        let up = Some(Direction::Up);
+        let mut heads = Vec::new();
+        for &input in &[up, up, up, up, up] {
+            let tick = game_state.tick();
+            game_state.update(input).unwrap();
+            trace.record(&game_state, input, tick).unwrap();
+            heads.push(game_state.snake_head());
+        }
+        assert!(game_state.game_over());
+        game_state.reset();
+        trace.record_reset(&game_state).unwrap();
+        trace.flush().unwrap();
+
+        let contents = fs::read_to_string(path).unwrap();
+        let lines = contents.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 4);
+        for (i, (line, head)) in lines.iter().zip(&heads[0..3]).enumerate() {
+            let record = serde_json::from_str::<serde_json::Value>(line).unwrap();
+            assert_eq!(record["tick"].as_u64(), Some(i as u64 + 1));
+            assert_eq!(record["head"], serde_json::to_value(head).unwrap());
+        }
+        assert_eq!(lines[3], r#"{"reset":true,"level":"default"}"#);
+        fs::remove_file(path).unwrap();
+    }
+}